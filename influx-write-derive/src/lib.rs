@@ -0,0 +1,214 @@
+//! Derive macro for `influx-write`, use it through the `derive` feature of `influx-write`
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, LitStr, PathArguments,
+    Type,
+};
+
+/// Implement `From<T> for DataPoint`
+///
+/// Every struct field needs one of `#[influx(tag)]`, `#[influx(field)]`, `#[influx(timestamp)]`
+/// or `#[influx(skip)]`. Tags and fields are named like the struct field unless renamed with
+/// `rename = "..."`, the measurement defaults to the struct name and can be set with
+/// `#[influx(measurement = "...")]` on the struct.
+///
+/// Tags are converted with `ToString`, fields with `Into<Value>` and the timestamp with
+/// `Into<Timestamp>`. `Option` tags, fields and timestamps are omitted when `None`. Like
+/// `DataPointBuilder`, at least one field must always be present, so at least one field has to be
+/// a non-optional `#[influx(field)]`.
+#[proc_macro_derive(IntoDataPoint, attributes(influx))]
+pub fn derive_into_data_point(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Tag,
+    Field,
+    Timestamp,
+    Skip,
+}
+
+struct Member {
+    ident: syn::Ident,
+    name: String,
+    kind: Kind,
+    optional: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut measurement = input.ident.to_string();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("influx")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("measurement") {
+                measurement = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `measurement = \"...\"`"))
+            }
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "IntoDataPoint requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "IntoDataPoint can only be derived for structs",
+            ))
+        }
+    };
+
+    let members = fields
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().expect("named field");
+            let mut name = ident.to_string();
+            let mut kind = None;
+
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("influx")) {
+                attr.parse_nested_meta(|meta| {
+                    let found = if meta.path.is_ident("tag") {
+                        Kind::Tag
+                    } else if meta.path.is_ident("field") {
+                        Kind::Field
+                    } else if meta.path.is_ident("timestamp") {
+                        Kind::Timestamp
+                    } else if meta.path.is_ident("skip") {
+                        Kind::Skip
+                    } else if meta.path.is_ident("rename") {
+                        name = meta.value()?.parse::<LitStr>()?.value();
+                        return Ok(());
+                    } else {
+                        return Err(meta.error(
+                            "expected `tag`, `field`, `timestamp`, `skip` or `rename = \"...\"`",
+                        ));
+                    };
+
+                    if kind.replace(found).is_some() {
+                        return Err(meta.error("only one of `tag`, `field`, `timestamp` or `skip`"));
+                    }
+                    Ok(())
+                })?;
+            }
+
+            let kind = kind.ok_or_else(|| {
+                Error::new(
+                    ident.span(),
+                    "missing #[influx(tag)], #[influx(field)], #[influx(timestamp)] or #[influx(skip)]",
+                )
+            })?;
+
+            Ok(Member {
+                optional: is_option(&field.ty),
+                ident,
+                name,
+                kind,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let timestamps: Vec<&Member> = members
+        .iter()
+        .filter(|m| matches!(m.kind, Kind::Timestamp))
+        .collect();
+    if let Some(second) = timestamps.get(1) {
+        return Err(Error::new(
+            second.ident.span(),
+            "only one field can be #[influx(timestamp)]",
+        ));
+    }
+
+    let Some(first_field) = members
+        .iter()
+        .position(|m| matches!(m.kind, Kind::Field) && !m.optional)
+    else {
+        return Err(Error::new(
+            Span::call_site(),
+            "IntoDataPoint requires at least one non-optional #[influx(field)], \
+             DataPoints must have at least one field",
+        ));
+    };
+
+    let first = &members[first_field];
+    let (first_ident, first_name) = (&first.ident, &first.name);
+
+    let steps = members
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != first_field)
+        .filter_map(|(_, member)| {
+            let ident = &member.ident;
+            let name = &member.name;
+
+            let step = |value: TokenStream2| match member.kind {
+                Kind::Tag => Some(
+                    quote!(builder.with_tag(#name, ::std::string::ToString::to_string(&#value))),
+                ),
+                Kind::Field => Some(quote!(builder.with_field(#name, #value))),
+                Kind::Timestamp => Some(quote!(builder.with_time(#value))),
+                Kind::Skip => None,
+            };
+
+            if member.optional {
+                let step = step(quote!(value))?;
+                Some(quote! {
+                    let builder = match source.#ident {
+                        ::std::option::Option::Some(value) => #step,
+                        ::std::option::Option::None => builder,
+                    };
+                })
+            } else {
+                let step = step(quote!(source.#ident))?;
+                Some(quote!(let builder = #step;))
+            }
+        });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::influx_write::DataPoint
+            #where_clause
+        {
+            fn from(source: #ident #ty_generics) -> Self {
+                let builder = ::influx_write::DataPointBuilder::new(#measurement)
+                    .with_field(#first_name, source.#first_ident);
+                #(#steps)*
+                builder.into()
+            }
+        }
+    })
+}
+
+/// Whether `ty` is spelled as `Option<T>`, possibly with a path like `std::option::Option<T>`
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+
+    path.qself.is_none()
+        && path.path.segments.last().is_some_and(|segment| {
+            segment.ident == "Option"
+                && matches!(
+                    &segment.arguments,
+                    PathArguments::AngleBracketed(args)
+                        if args.args.len() == 1
+                            && matches!(args.args[0], GenericArgument::Type(_))
+                )
+        })
+}
@@ -1,17 +1,75 @@
-use anyhow::bail;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use http::Method;
 use log::trace;
+use serde::de::DeserializeOwned;
 use url::Url;
 
-use crate::{API_ENDPOINT_V2, Authorization, DataPoint, InfluxWriter, WritePrecision};
+use crate::delete::DeletePredicate;
+use crate::health::{parse_health, parse_ping};
+use crate::query::{FluxRecordStream, FluxTable};
+use crate::sql::{parse_influxql, parse_jsonl, Series};
+use crate::{
+    error_for_status, status_error, Authorization, DataPoint, Health, InfluxWriter, Ping, Rounding,
+    Timestamp, WritePrecision, API_ENDPOINT_HEALTH, API_ENDPOINT_PING, API_ENDPOINT_V2,
+};
 
+#[cfg(feature = "hyper")]
+pub mod hyper;
 #[cfg(feature = "reqwest")]
 pub mod reqwest;
+#[cfg(feature = "tokio")]
+pub mod stream;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "tokio")]
+pub mod udp;
 
 pub trait AsyncClient {
     fn execute(
         &mut self,
         req: http::Request<String>,
-    ) -> impl std::future::Future<Output = anyhow::Result<http::Response<Vec<u8>>>> + Send;
+    ) -> impl Future<Output = anyhow::Result<http::Response<Vec<u8>>>> + Send;
+
+    /// Like [AsyncClient::execute], but the body is handed out while it is still being received
+    ///
+    /// Used for query results, which may be large. The default implementation buffers the whole
+    /// body with [AsyncClient::execute].
+    fn execute_streaming(
+        &mut self,
+        req: http::Request<String>,
+    ) -> impl Future<Output = anyhow::Result<StreamingResponse>> + Send {
+        let response = self.execute(req);
+
+        async move {
+            Ok(response
+                .await?
+                .map(|body| Box::new(body) as Box<dyn AsyncBody>))
+        }
+    }
+}
+
+pub type StreamingResponse = http::Response<Box<dyn AsyncBody>>;
+
+pub type ChunkFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<Option<Vec<u8>>>> + Send + 'a>>;
+
+/// Response body that is received in chunks, see [AsyncClient::execute_streaming]
+pub trait AsyncBody: Send {
+    /// The next chunk of the body, `None` once it is complete
+    fn next_chunk(&mut self) -> ChunkFuture<'_>;
+}
+
+/// A body that has already been received completely is a single chunk
+impl AsyncBody for Vec<u8> {
+    fn next_chunk(&mut self) -> ChunkFuture<'_> {
+        let chunk = std::mem::take(self);
+
+        Box::pin(async move { Ok((!chunk.is_empty()).then_some(chunk)) })
+    }
 }
 
 impl<W: AsyncClient> InfluxWriter<W> {
@@ -28,6 +86,11 @@ impl<W: AsyncClient> InfluxWriter<W> {
             authorization,
             org: org.into(),
             bucket: bucket.into(),
+            default_tags: HashMap::new(),
+            clock: None,
+            rounding: Rounding::default(),
+            schema: None,
+            cardinality_guard: None,
         })
     }
 
@@ -59,7 +122,9 @@ impl<W: AsyncClient> InfluxWriter<W> {
         points: impl IntoIterator<Item = DataPoint>,
         precision: WritePrecision,
     ) -> anyhow::Result<()> {
-        let req = self.build_request(points, precision)?;
+        let Some(req) = self.build_request(points, precision)? else {
+            return Ok(());
+        };
 
         trace!("Sending request: {:?}", req);
 
@@ -67,13 +132,112 @@ impl<W: AsyncClient> InfluxWriter<W> {
 
         trace!("Got response: {:?}", response);
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            bail!(
-                "Got response: {:?}",
-                String::from_utf8(response.body().clone())
-            )
+        error_for_status(response).map(|_| ())
+    }
+
+    /// Check that the server is reachable, returns its version and build
+    pub async fn ping(&mut self) -> anyhow::Result<Ping> {
+        let req = self.build_api_request(Method::GET, API_ENDPOINT_PING, &[], String::new())?;
+
+        parse_ping(self.client.execute(req).await?)
+    }
+
+    /// Query the server's health, a failing server is reported as [crate::HealthStatus::Fail]
+    pub async fn health(&mut self) -> anyhow::Result<Health> {
+        let req = self.build_api_request(Method::GET, API_ENDPOINT_HEALTH, &[], String::new())?;
+
+        parse_health(self.client.execute(req).await?)
+    }
+
+    /// Check that the bucket exists and the token may write to it
+    ///
+    /// Errors describing the problem are [crate::buckets::VerifyError]s.
+    pub async fn verify(&mut self) -> anyhow::Result<()> {
+        self.verify_with(None).await
+    }
+
+    /// Like [InfluxWriter::verify], but create a missing bucket with the given retention period
+    ///
+    /// Without a retention period the bucket keeps data forever.
+    pub async fn verify_or_create_bucket(
+        &mut self,
+        retention: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        self.verify_with(Some(retention)).await
+    }
+
+    async fn verify_with(&mut self, create: Option<Option<Duration>>) -> anyhow::Result<()> {
+        let req = self.build_find_bucket_request()?;
+        let response = self.client.execute(req).await?;
+
+        if self.check_find_bucket_response(response, create.is_some())? {
+            let req = self.build_find_org_request()?;
+            let response = self.client.execute(req).await?;
+            let req = self.build_create_bucket_request(response, create.flatten())?;
+            error_for_status(self.client.execute(req).await?)?;
         }
+
+        let req = self.build_write_permission_request()?;
+        let response = self.client.execute(req).await?;
+        self.check_write_permission_response(response)
+    }
+
+    /// Delete points of the writer's bucket in the time range `start..stop`
+    ///
+    /// Only series matching `predicate` are deleted, an empty predicate deletes everything.
+    pub async fn delete(
+        &mut self,
+        start: impl Into<Timestamp>,
+        stop: impl Into<Timestamp>,
+        predicate: &DeletePredicate,
+    ) -> anyhow::Result<()> {
+        let req = self.build_delete_request(start.into(), stop.into(), predicate)?;
+
+        error_for_status(self.client.execute(req).await?).map(|_| ())
+    }
+
+    /// Run a Flux query in the writer's org and collect the result tables
+    pub async fn query(&mut self, flux: &str) -> anyhow::Result<Vec<FluxTable>> {
+        self.query_records(flux).await?.into_tables().await
+    }
+
+    /// Run a Flux query in the writer's org and decode the records while they are received
+    ///
+    /// Clients that don't implement [AsyncClient::execute_streaming] buffer the whole body first.
+    pub async fn query_records(&mut self, flux: &str) -> anyhow::Result<FluxRecordStream> {
+        let req = self.build_query_request(flux)?;
+        let response = self.client.execute_streaming(req).await?;
+        let (parts, mut body) = response.into_parts();
+
+        if !parts.status.is_success() {
+            let mut bytes = Vec::new();
+            while let Some(chunk) = body.next_chunk().await? {
+                bytes.extend(chunk);
+            }
+            return Err(status_error(&bytes));
+        }
+
+        Ok(FluxRecordStream::new(body))
+    }
+
+    /// Run a SQL query against the writer's bucket on InfluxDB 3
+    ///
+    /// Rows are deserialized into `T`, use [crate::sql::Row] to get them keyed by column name.
+    pub async fn query_sql<T: DeserializeOwned>(&mut self, sql: &str) -> anyhow::Result<Vec<T>> {
+        let req = self.build_sql_request(sql)?;
+        let response = error_for_status(self.client.execute(req).await?)?;
+
+        Ok(parse_jsonl(response.body())?)
+    }
+
+    /// Run an InfluxQL query against the writer's bucket on InfluxDB 1.x
+    ///
+    /// The series of all statements are returned in order, rows can be deserialized with
+    /// [Series::deserialize_rows].
+    pub async fn query_influxql(&mut self, influxql: &str) -> anyhow::Result<Vec<Series>> {
+        let req = self.build_influxql_request(influxql)?;
+        let response = error_for_status(self.client.execute(req).await?)?;
+
+        Ok(parse_influxql(response.body())?)
     }
 }
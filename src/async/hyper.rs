@@ -0,0 +1,104 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper_util::client::legacy::connect::{Connect, HttpConnector};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use url::Url;
+
+use crate::buckets::BucketsClient;
+use crate::{AsyncBody, AsyncClient, Authorization, ChunkFuture, InfluxWriter, StreamingResponse};
+
+#[cfg(feature = "hyper-tls")]
+pub type DefaultConnector = hyper_tls::HttpsConnector<HttpConnector>;
+#[cfg(not(feature = "hyper-tls"))]
+pub type DefaultConnector = HttpConnector;
+
+/// [AsyncClient] backed by the pooled `hyper-util` legacy client
+///
+/// With the `hyper-tls` feature enabled the default connector also supports `https` urls.
+#[derive(Clone)]
+pub struct HyperClient<C = DefaultConnector> {
+    client: Client<C, Full<Bytes>>,
+}
+
+impl HyperClient {
+    pub(crate) fn new() -> Self {
+        Self::from_client(Client::builder(TokioExecutor::new()).build(default_connector()))
+    }
+}
+
+impl<C> HyperClient<C> {
+    /// Use an already configured client, e.g. with custom pool settings or connector
+    pub fn from_client(client: Client<C, Full<Bytes>>) -> Self {
+        Self { client }
+    }
+}
+
+impl<C> AsyncClient for HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    async fn execute(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<http::Response<Vec<u8>>> {
+        let response = self.client.request(req.map(Full::from)).await?;
+        let (parts, body) = response.into_parts();
+
+        Ok(http::Response::from_parts(
+            parts,
+            body.collect().await?.to_bytes().to_vec(),
+        ))
+    }
+
+    async fn execute_streaming(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<StreamingResponse> {
+        let response = self.client.request(req.map(Full::from)).await?;
+
+        Ok(response.map(|body| Box::new(body) as Box<dyn AsyncBody>))
+    }
+}
+
+impl AsyncBody for Incoming {
+    fn next_chunk(&mut self) -> ChunkFuture<'_> {
+        Box::pin(async move {
+            while let Some(frame) = self.frame().await {
+                // trailers are skipped, only data frames are part of the body
+                if let Ok(data) = frame?.into_data() {
+                    return Ok(Some(data.to_vec()));
+                }
+            }
+            Ok(None)
+        })
+    }
+}
+
+impl InfluxWriter<HyperClient> {
+    pub fn new(
+        url: Url,
+        authorization: Authorization,
+        org: impl Into<String>,
+        bucket: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_client(HyperClient::new(), url, authorization, org, bucket)
+    }
+}
+
+impl BucketsClient<HyperClient> {
+    pub fn new(url: Url, authorization: Authorization) -> Self {
+        Self::new_with_client(HyperClient::new(), url, authorization)
+    }
+}
+
+#[cfg(feature = "hyper-tls")]
+fn default_connector() -> DefaultConnector {
+    hyper_tls::HttpsConnector::new()
+}
+
+#[cfg(not(feature = "hyper-tls"))]
+fn default_connector() -> DefaultConnector {
+    HttpConnector::new()
+}
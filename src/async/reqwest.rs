@@ -1,8 +1,13 @@
 use reqwest::{Client, ClientBuilder, Request, Response};
 use url::Url;
 
-use crate::{AsyncClient, Authorization, HttpClientError, InfluxWriter};
+use crate::buckets::BucketsClient;
+use crate::{
+    AsyncBody, AsyncClient, Authorization, ChunkFuture, HttpClientError, InfluxWriter,
+    StreamingResponse,
+};
 
+#[derive(Clone)]
 pub struct ReqwestClient {
     client: Client,
 }
@@ -23,6 +28,20 @@ impl AsyncClient for ReqwestClient {
         let response = self.client.execute(convert_request(req)?).await?;
         convert_response(response).await
     }
+
+    async fn execute_streaming(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<StreamingResponse> {
+        let response = self.client.execute(convert_request(req)?).await?;
+        Ok(response_builder(&response).body(Box::new(response) as Box<dyn AsyncBody>)?)
+    }
+}
+
+impl AsyncBody for Response {
+    fn next_chunk(&mut self) -> ChunkFuture<'_> {
+        Box::pin(async move { Ok(self.chunk().await?.map(|chunk| chunk.to_vec())) })
+    }
 }
 
 impl InfluxWriter<ReqwestClient> {
@@ -36,6 +55,16 @@ impl InfluxWriter<ReqwestClient> {
     }
 }
 
+impl BucketsClient<ReqwestClient> {
+    pub fn new(url: Url, authorization: Authorization) -> anyhow::Result<Self> {
+        Ok(Self::new_with_client(
+            ReqwestClient::new()?,
+            url,
+            authorization,
+        ))
+    }
+}
+
 fn convert_request<T>(
     req: http::Request<T>,
 ) -> Result<Request, <Request as TryFrom<http::Request<T>>>::Error>
@@ -46,12 +75,16 @@ where
 }
 
 async fn convert_response(resp: Response) -> anyhow::Result<http::Response<Vec<u8>>> {
-    let mut response = http::response::Builder::new();
+    Ok(response_builder(&resp).body(resp.bytes().await?.to_vec())?)
+}
+
+fn response_builder(resp: &Response) -> http::response::Builder {
+    let mut response = http::response::Builder::new().status(resp.status());
 
     response.headers_mut().unwrap().extend(
         resp.headers()
             .into_iter()
             .map(|(k, v)| (k.clone(), v.clone())),
     );
-    Ok(response.body(resp.bytes().await?.to_vec())?)
+    response
 }
@@ -0,0 +1,157 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use log::debug;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::time::timeout;
+
+#[cfg(unix)]
+use crate::blocking::stream::UnixConnector;
+use crate::blocking::stream::{TcpConnector, DEFAULT_BUFFER_CAPACITY};
+use crate::influx::LineProtocol;
+use crate::{DataPoint, WritePrecision};
+
+/// Establishes the connection an [AsyncStreamWriter] writes to, called again after failures
+pub trait AsyncConnector {
+    type Stream: AsyncWrite + Unpin + Send;
+
+    fn connect(&self) -> impl Future<Output = io::Result<Self::Stream>> + Send;
+
+    /// Time the peer gets to accept a write before it fails, `None` waits indefinitely
+    fn write_timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl AsyncConnector for TcpConnector {
+    type Stream = TcpStream;
+
+    async fn connect(&self) -> io::Result<TcpStream> {
+        match self.connect_timeout {
+            None => TcpStream::connect(&self.addrs[..]).await,
+            Some(duration) => timeout(duration, TcpStream::connect(&self.addrs[..]))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))?,
+        }
+    }
+
+    fn write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
+}
+
+#[cfg(unix)]
+impl AsyncConnector for UnixConnector {
+    type Stream = UnixStream;
+
+    async fn connect(&self) -> io::Result<UnixStream> {
+        UnixStream::connect(&self.path).await
+    }
+}
+
+/// Streams newline terminated line protocol over a persistent connection
+///
+/// Every write is flushed before it returns, nothing is held back between writes, the buffer only
+/// coalesces the socket writes of one call. Flushing waits while the peer does not keep up, at
+/// most for the connector's write timeout. If writing fails the connection is re-established and
+/// the batch is sent once more, so points may be delivered twice when the failure happened after
+/// the peer already received them.
+pub struct AsyncStreamWriter<C: AsyncConnector> {
+    connector: C,
+    stream: Option<BufWriter<C::Stream>>,
+    buffer_capacity: usize,
+}
+
+pub type AsyncTcpWriter = AsyncStreamWriter<TcpConnector>;
+#[cfg(unix)]
+pub type AsyncUnixWriter = AsyncStreamWriter<UnixConnector>;
+
+impl<C: AsyncConnector> AsyncStreamWriter<C> {
+    /// Create the writer, the connection is established lazily on the first write
+    pub fn new(connector: C) -> Self {
+        Self {
+            connector,
+            stream: None,
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+        }
+    }
+
+    pub fn with_buffer_capacity(mut self, buffer_capacity: usize) -> Self {
+        self.buffer_capacity = buffer_capacity;
+        self
+    }
+
+    pub async fn write_single(&mut self, point: DataPoint) -> anyhow::Result<()> {
+        self.write(vec![point]).await
+    }
+
+    /// Write point with specified precision
+    pub async fn write_single_with_precision(
+        &mut self,
+        point: DataPoint,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(vec![point], precision).await
+    }
+
+    /// Write points with default precision
+    pub async fn write(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(points, WritePrecision::default())
+            .await
+    }
+
+    /// Write points with specified precision
+    pub async fn write_with_precision(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        let mut body = points.to_line_protocol(precision)?;
+        if body.is_empty() {
+            return Ok(());
+        }
+        body.push('\n');
+
+        if let Err(e) = self.send(body.as_bytes()).await {
+            debug!("Writing to stream failed, reconnecting: {e}");
+
+            self.send(body.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self.stream.insert(BufWriter::with_capacity(
+                self.buffer_capacity,
+                self.connector.connect().await?,
+            )),
+        };
+
+        let send = async {
+            stream.write_all(buf).await?;
+            stream.flush().await
+        };
+        let result = match self.connector.write_timeout() {
+            None => send.await,
+            Some(duration) => timeout(duration, send).await.unwrap_or_else(|_| {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "write timed out"))
+            }),
+        };
+        if result.is_err() {
+            // the buffered remainder belongs to a broken connection, discard it with the stream
+            self.stream = None;
+        }
+
+        result
+    }
+}
@@ -0,0 +1,145 @@
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http_body_util::BodyExt;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::clock::Clock;
+use crate::{
+    error_for_status, stamp_untimed, AsyncClient, DataPoint, InfluxWriter, WritePrecision,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// [AsyncClient] adapter for any `tower` http service
+///
+/// This allows wrapping the transport in tower middleware (timeouts, retries, rate limits, ...).
+#[derive(Clone)]
+pub struct TowerClient<S> {
+    service: S,
+}
+
+impl<S> TowerClient<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S, B> AsyncClient for TowerClient<S>
+where
+    S: Service<http::Request<String>, Response = http::Response<B>> + Send,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: http_body::Body + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    async fn execute(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<http::Response<Vec<u8>>> {
+        poll_fn(|cx| self.service.poll_ready(cx))
+            .await
+            .map_err(|e| anyhow::Error::from_boxed(e.into()))?;
+
+        let response = self
+            .service
+            .call(req)
+            .await
+            .map_err(|e| anyhow::Error::from_boxed(e.into()))?;
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| anyhow::Error::from_boxed(e.into()))?;
+
+        Ok(http::Response::from_parts(parts, body.to_bytes().to_vec()))
+    }
+}
+
+/// Expose writes as a `tower` service so layers can be composed around them
+///
+/// Every call writes the given points with default precision using a clone of the client. Clones
+/// of the writer share its schema registry and cardinality guard, so it can be wrapped in layers
+/// that clone their service, like retries.
+impl<W> Service<Vec<DataPoint>> for InfluxWriter<W>
+where
+    W: AsyncClient + Clone + Send + 'static,
+{
+    type Response = ();
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, points: Vec<DataPoint>) -> Self::Future {
+        let req = self.build_request(points, WritePrecision::default());
+        let mut client = self.client.clone();
+
+        Box::pin(async move {
+            let Some(req) = req? else {
+                return Ok(());
+            };
+            error_for_status(client.execute(req).await?).map(|_| ())
+        })
+    }
+}
+
+/// Layer stamping points without a timestamp with the time `clock` reports when they are accepted
+///
+/// Put it outside of retry layers, so that all attempts of a write send the same timestamp.
+/// Points that already have a timestamp keep it.
+#[derive(Clone)]
+pub struct ClientTimestampLayer {
+    clock: Arc<dyn Clock>,
+}
+
+impl ClientTimestampLayer {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Arc::new(clock),
+        }
+    }
+}
+
+impl<S> Layer<S> for ClientTimestampLayer {
+    type Service = ClientTimestamps<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientTimestamps {
+            inner,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+/// Service created by [ClientTimestampLayer]
+#[derive(Clone)]
+pub struct ClientTimestamps<S> {
+    inner: S,
+    clock: Arc<dyn Clock>,
+}
+
+impl<S: Service<Vec<DataPoint>>> Service<Vec<DataPoint>> for ClientTimestamps<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut points: Vec<DataPoint>) -> Self::Future {
+        stamp_untimed(&mut points, self.clock.as_ref());
+        self.inner.call(points)
+    }
+}
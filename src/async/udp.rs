@@ -0,0 +1,79 @@
+use std::io;
+
+use tokio::net::{lookup_host, ToSocketAddrs, UdpSocket};
+
+use crate::blocking::udp::{unspecified_addr, DEFAULT_MAX_DATAGRAM_SIZE};
+use crate::influx::to_line_protocol_chunks;
+use crate::{DataPoint, WritePrecision};
+
+/// Fire-and-forget line protocol writer for InfluxDB 1.x / Telegraf UDP listeners
+pub struct AsyncUdpWriter {
+    socket: UdpSocket,
+    max_datagram_size: usize,
+}
+
+impl AsyncUdpWriter {
+    /// Bind an ephemeral local socket and connect it to `addr`
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addr = lookup_host(addr).await?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no socket address to connect to",
+            )
+        })?;
+
+        let socket = UdpSocket::bind(unspecified_addr(&addr)).await?;
+        socket.connect(addr).await?;
+
+        Ok(Self::from_socket(socket))
+    }
+
+    /// Use an already connected socket
+    pub fn from_socket(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        }
+    }
+
+    /// Set the maximum payload per datagram, points are packed into datagrams up to this size
+    pub fn with_max_datagram_size(mut self, max_datagram_size: usize) -> Self {
+        self.max_datagram_size = max_datagram_size;
+        self
+    }
+
+    pub async fn write_single(&mut self, point: DataPoint) -> anyhow::Result<()> {
+        self.write(vec![point]).await
+    }
+
+    /// Write point with specified precision
+    pub async fn write_single_with_precision(
+        &mut self,
+        point: DataPoint,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(vec![point], precision).await
+    }
+
+    /// Write points with default precision
+    pub async fn write(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(points, WritePrecision::default())
+            .await
+    }
+
+    /// Write points with specified precision
+    pub async fn write_with_precision(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        for datagram in to_line_protocol_chunks(points, precision, self.max_datagram_size)? {
+            self.socket.send(datagram.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+}
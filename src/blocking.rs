@@ -1,13 +1,43 @@
-use anyhow::bail;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+use http::Method;
+use serde::de::DeserializeOwned;
 use url::Url;
 
-use crate::{Authorization, DataPoint, InfluxWriter, WritePrecision, API_ENDPOINT_V2};
+use crate::delete::DeletePredicate;
+use crate::health::{parse_health, parse_ping};
+use crate::query::{FluxCsvReader, FluxTable};
+use crate::sql::{parse_influxql, parse_jsonl, Series};
+use crate::{
+    error_for_status, status_error, Authorization, DataPoint, Health, InfluxWriter, Ping, Rounding,
+    Timestamp, WritePrecision, API_ENDPOINT_HEALTH, API_ENDPOINT_PING, API_ENDPOINT_V2,
+};
 
+pub mod file;
 #[cfg(feature = "reqwest-blocking")]
 pub mod reqwest;
+pub mod stream;
+pub mod udp;
+#[cfg(feature = "ureq")]
+pub mod ureq;
 
 pub trait BlockingClient {
     fn execute(&mut self, req: http::Request<String>) -> anyhow::Result<http::Response<Vec<u8>>>;
+
+    /// Like [BlockingClient::execute], but the body is read while it is still being received
+    ///
+    /// Used for query results, which may be large. The default implementation buffers the whole
+    /// body with [BlockingClient::execute].
+    fn execute_streaming(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<http::Response<Box<dyn Read + Send>>> {
+        Ok(self
+            .execute(req)?
+            .map(|body| Box::new(Cursor::new(body)) as Box<dyn Read + Send>))
+    }
 }
 
 impl<W: BlockingClient> InfluxWriter<W> {
@@ -24,6 +54,11 @@ impl<W: BlockingClient> InfluxWriter<W> {
             authorization,
             org: org.into(),
             bucket: bucket.into(),
+            default_tags: HashMap::new(),
+            clock: None,
+            rounding: Rounding::default(),
+            schema: None,
+            cardinality_guard: None,
         })
     }
 
@@ -54,17 +89,120 @@ impl<W: BlockingClient> InfluxWriter<W> {
         points: impl IntoIterator<Item = DataPoint>,
         precision: WritePrecision,
     ) -> anyhow::Result<()> {
-        let req = self.build_request(points, precision)?;
+        let Some(req) = self.build_request(points, precision)? else {
+            return Ok(());
+        };
 
         let response = self.client.execute(req)?;
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            bail!(
-                "Got response: {:?}",
-                String::from_utf8(response.body().clone())
-            )
+        error_for_status(response).map(|_| ())
+    }
+
+    /// Check that the server is reachable, returns its version and build
+    pub fn ping_blocking(&mut self) -> anyhow::Result<Ping> {
+        let req = self.build_api_request(Method::GET, API_ENDPOINT_PING, &[], String::new())?;
+
+        parse_ping(self.client.execute(req)?)
+    }
+
+    /// Query the server's health, a failing server is reported as [crate::HealthStatus::Fail]
+    pub fn health_blocking(&mut self) -> anyhow::Result<Health> {
+        let req = self.build_api_request(Method::GET, API_ENDPOINT_HEALTH, &[], String::new())?;
+
+        parse_health(self.client.execute(req)?)
+    }
+
+    /// Check that the bucket exists and the token may write to it
+    ///
+    /// Errors describing the problem are [crate::buckets::VerifyError]s.
+    pub fn verify_blocking(&mut self) -> anyhow::Result<()> {
+        self.verify_with_blocking(None)
+    }
+
+    /// Like [InfluxWriter::verify_blocking], but create a missing bucket with the given retention
+    /// period
+    ///
+    /// Without a retention period the bucket keeps data forever.
+    pub fn verify_or_create_bucket_blocking(
+        &mut self,
+        retention: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        self.verify_with_blocking(Some(retention))
+    }
+
+    fn verify_with_blocking(&mut self, create: Option<Option<Duration>>) -> anyhow::Result<()> {
+        let req = self.build_find_bucket_request()?;
+        let response = self.client.execute(req)?;
+
+        if self.check_find_bucket_response(response, create.is_some())? {
+            let req = self.build_find_org_request()?;
+            let response = self.client.execute(req)?;
+            let req = self.build_create_bucket_request(response, create.flatten())?;
+            error_for_status(self.client.execute(req)?)?;
         }
+
+        let req = self.build_write_permission_request()?;
+        let response = self.client.execute(req)?;
+        self.check_write_permission_response(response)
+    }
+
+    /// Delete points of the writer's bucket in the time range `start..stop`
+    ///
+    /// Only series matching `predicate` are deleted, an empty predicate deletes everything.
+    pub fn delete_blocking(
+        &mut self,
+        start: impl Into<Timestamp>,
+        stop: impl Into<Timestamp>,
+        predicate: &DeletePredicate,
+    ) -> anyhow::Result<()> {
+        let req = self.build_delete_request(start.into(), stop.into(), predicate)?;
+
+        error_for_status(self.client.execute(req)?).map(|_| ())
+    }
+
+    /// Run a Flux query in the writer's org and collect the result tables
+    pub fn query_blocking(&mut self, flux: &str) -> anyhow::Result<Vec<FluxTable>> {
+        Ok(self.query_records_blocking(flux)?.into_tables()?)
+    }
+
+    /// Run a Flux query in the writer's org and decode the records while they are received
+    ///
+    /// Clients that don't implement [BlockingClient::execute_streaming] buffer the whole body
+    /// first.
+    pub fn query_records_blocking(
+        &mut self,
+        flux: &str,
+    ) -> anyhow::Result<FluxCsvReader<Box<dyn Read + Send>>> {
+        let req = self.build_query_request(flux)?;
+        let (parts, mut body) = self.client.execute_streaming(req)?.into_parts();
+
+        if !parts.status.is_success() {
+            let mut bytes = Vec::new();
+            body.read_to_end(&mut bytes)?;
+            return Err(status_error(&bytes));
+        }
+
+        Ok(FluxCsvReader::new(body))
+    }
+
+    /// Run a SQL query against the writer's bucket on InfluxDB 3
+    ///
+    /// Rows are deserialized into `T`, use [crate::sql::Row] to get them keyed by column name.
+    pub fn query_sql_blocking<T: DeserializeOwned>(&mut self, sql: &str) -> anyhow::Result<Vec<T>> {
+        let req = self.build_sql_request(sql)?;
+        let response = error_for_status(self.client.execute(req)?)?;
+
+        Ok(parse_jsonl(response.body())?)
+    }
+
+    /// Run an InfluxQL query against the writer's bucket on InfluxDB 1.x
+    ///
+    /// The series of all statements are returned in order, rows can be deserialized with
+    /// [Series::deserialize_rows].
+    pub fn query_influxql_blocking(&mut self, influxql: &str) -> anyhow::Result<Vec<Series>> {
+        let req = self.build_influxql_request(influxql)?;
+        let response = error_for_status(self.client.execute(req)?)?;
+
+        Ok(parse_influxql(response.body())?)
     }
 }
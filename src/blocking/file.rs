@@ -0,0 +1,310 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::influx::LineProtocol;
+use crate::{DataPoint, WritePrecision};
+
+/// Writes the line protocol bodies that would be sent to the HTTP API into any [Write]
+///
+/// Every batch is terminated by a newline, so the output can be imported with `influx write`.
+pub struct IoWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> IoWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write_single(&mut self, point: DataPoint) -> anyhow::Result<()> {
+        self.write(vec![point])
+    }
+
+    /// Write point with specified precision
+    pub fn write_single_with_precision(
+        &mut self,
+        point: DataPoint,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(vec![point], precision)
+    }
+
+    /// Write points with default precision
+    pub fn write(&mut self, points: impl IntoIterator<Item = DataPoint>) -> anyhow::Result<()> {
+        self.write_with_precision(points, WritePrecision::default())
+    }
+
+    /// Write points with specified precision
+    pub fn write_with_precision(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        let body = points.to_line_protocol(precision)?;
+        if !body.is_empty() {
+            self.writer.write_all(body.as_bytes())?;
+            self.writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl IoWriter<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+/// When a [FileWriter] starts a new file
+#[derive(Clone, Debug, Default)]
+pub struct Rotation {
+    /// Rotate once the current file has this many bytes on disk, i.e. after compression
+    pub max_size: Option<u64>,
+    /// Rotate once the current file has been open for this long
+    pub max_age: Option<Duration>,
+}
+
+/// Writes line protocol bodies to a file, optionally rotating and gzip compressing it
+///
+/// Rotated files are renamed to `<path>.<UTC timestamp>` and a new file is started at `path`. With
+/// gzip the `.gz` extension stays last, `points.lp.gz` is rotated to
+/// `points.lp.<UTC timestamp>.gz`. Rotation is only checked when writing, no background thread is
+/// involved.
+pub struct FileWriter {
+    path: PathBuf,
+    rotation: Rotation,
+    #[cfg(feature = "gzip")]
+    gzip: Option<flate2::Compression>,
+    file: Option<OpenFile>,
+}
+
+struct OpenFile {
+    writer: FileSink,
+    opened_at: Instant,
+}
+
+enum FileSink {
+    Plain(BufWriter<CountingFile>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<BufWriter<CountingFile>>),
+}
+
+/// Keeps track of the file size, starting from the size of the file when it was opened
+struct CountingFile {
+    file: File,
+    len: u64,
+}
+
+impl FileWriter {
+    /// Create the writer, appending to `path` if it already exists
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            rotation: Rotation::default(),
+            #[cfg(feature = "gzip")]
+            gzip: None,
+            file: None,
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Compress files with gzip, appending to an existing file adds a new gzip member
+    #[cfg(feature = "gzip")]
+    pub fn with_gzip(mut self, level: flate2::Compression) -> Self {
+        self.gzip = Some(level);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_single(&mut self, point: DataPoint) -> anyhow::Result<()> {
+        self.write(vec![point])
+    }
+
+    /// Write point with specified precision
+    pub fn write_single_with_precision(
+        &mut self,
+        point: DataPoint,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(vec![point], precision)
+    }
+
+    /// Write points with default precision
+    pub fn write(&mut self, points: impl IntoIterator<Item = DataPoint>) -> anyhow::Result<()> {
+        self.write_with_precision(points, WritePrecision::default())
+    }
+
+    /// Write points with specified precision
+    pub fn write_with_precision(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        let mut body = points.to_line_protocol(precision)?;
+        if body.is_empty() {
+            return Ok(());
+        }
+        body.push('\n');
+
+        if self.needs_rotation() {
+            self.rotate()?;
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(self.open()?),
+        };
+
+        file.writer.write_all(body.as_bytes())?;
+        file.writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Finish the current file and move it aside, the next write starts a new file
+    pub fn rotate(&mut self) -> io::Result<()> {
+        if let Some(file) = self.file.take() {
+            file.writer.finish()?;
+        } else if !self.path.exists() {
+            return Ok(());
+        }
+
+        fs::rename(&self.path, self.rotated_path())
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let timestamp = Utc::now().format(".%Y%m%dT%H%M%S%.9fZ").to_string();
+
+        #[cfg(feature = "gzip")]
+        if self.gzip.is_some() {
+            let mut rotated = match self.path.extension() {
+                Some(extension) if extension == "gz" => self.path.with_extension(""),
+                _ => self.path.clone(),
+            }
+            .into_os_string();
+            rotated.push(timestamp);
+            rotated.push(".gz");
+            return rotated.into();
+        }
+
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(timestamp);
+        rotated.into()
+    }
+
+    fn needs_rotation(&self) -> bool {
+        let Some(file) = &self.file else {
+            // a file left by an earlier writer only counts towards the size, its age is unknown
+            return self.rotation.max_size.is_some_and(|max_size| {
+                fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() >= max_size)
+            });
+        };
+
+        self.rotation
+            .max_size
+            .is_some_and(|max_size| file.writer.len() >= max_size)
+            || self
+                .rotation
+                .max_age
+                .is_some_and(|max_age| file.opened_at.elapsed() >= max_age)
+    }
+
+    fn open(&self) -> io::Result<OpenFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let len = file.metadata()?.len();
+        let writer = BufWriter::new(CountingFile { file, len });
+
+        #[cfg(feature = "gzip")]
+        let writer = match self.gzip {
+            Some(level) => FileSink::Gzip(flate2::write::GzEncoder::new(writer, level)),
+            None => FileSink::Plain(writer),
+        };
+        #[cfg(not(feature = "gzip"))]
+        let writer = FileSink::Plain(writer);
+
+        Ok(OpenFile {
+            writer,
+            opened_at: Instant::now(),
+        })
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            let _ = file.writer.finish();
+        }
+    }
+}
+
+impl FileSink {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            FileSink::Plain(mut writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            FileSink::Gzip(writer) => writer.finish()?.flush(),
+        }
+    }
+
+    /// Size of the file on disk, not counting data still buffered
+    fn len(&self) -> u64 {
+        match self {
+            FileSink::Plain(writer) => writer.get_ref().len,
+            #[cfg(feature = "gzip")]
+            FileSink::Gzip(writer) => writer.get_ref().get_ref().len,
+        }
+    }
+}
+
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Write for FileSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FileSink::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "gzip")]
+            FileSink::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FileSink::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            FileSink::Gzip(writer) => writer.flush(),
+        }
+    }
+}
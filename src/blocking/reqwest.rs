@@ -1,9 +1,13 @@
+use std::io::Read;
+
 use reqwest::blocking::{Client, ClientBuilder, Request, Response};
 use url::Url;
 
 use crate::blocking::BlockingClient;
+use crate::buckets::BucketsClient;
 use crate::{Authorization, HttpClientError, InfluxWriter};
 
+#[derive(Clone)]
 pub struct ReqwestClient {
     client: Client,
 }
@@ -21,6 +25,14 @@ impl BlockingClient for ReqwestClient {
         let response = self.client.execute(convert_request(req)?)?;
         convert_response(response)
     }
+
+    fn execute_streaming(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<http::Response<Box<dyn Read + Send>>> {
+        let response = self.client.execute(convert_request(req)?)?;
+        Ok(response_builder(&response).body(Box::new(response) as Box<dyn Read + Send>)?)
+    }
 }
 
 impl InfluxWriter<ReqwestClient> {
@@ -34,6 +46,16 @@ impl InfluxWriter<ReqwestClient> {
     }
 }
 
+impl BucketsClient<ReqwestClient> {
+    pub fn new(url: Url, authorization: Authorization) -> anyhow::Result<Self> {
+        Ok(Self::new_with_blocking_client(
+            ReqwestClient::new()?,
+            url,
+            authorization,
+        ))
+    }
+}
+
 fn convert_request<T>(
     req: http::Request<T>,
 ) -> Result<Request, <Request as TryFrom<http::Request<T>>>::Error>
@@ -44,12 +66,16 @@ where
 }
 
 fn convert_response(resp: Response) -> anyhow::Result<http::Response<Vec<u8>>> {
-    let mut response = http::response::Builder::new();
+    Ok(response_builder(&resp).body(resp.bytes()?.to_vec())?)
+}
+
+fn response_builder(resp: &Response) -> http::response::Builder {
+    let mut response = http::response::Builder::new().status(resp.status());
 
     response.headers_mut().unwrap().extend(
         resp.headers()
             .into_iter()
             .map(|(k, v)| (k.clone(), v.clone())),
     );
-    Ok(response.body(resp.bytes()?.to_vec())?)
+    response
 }
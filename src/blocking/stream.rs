@@ -0,0 +1,198 @@
+use std::io::{self, BufWriter, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::debug;
+
+use crate::influx::LineProtocol;
+use crate::{DataPoint, WritePrecision};
+
+/// Default capacity of the write buffer in front of the connection
+pub const DEFAULT_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// Establishes the connection a [StreamWriter] writes to, called again after failures
+pub trait Connector {
+    type Stream: Write;
+
+    fn connect(&self) -> io::Result<Self::Stream>;
+}
+
+/// Connects to a TCP listener, e.g. Telegraf's `socket_listener` with `tcp://`
+#[derive(Clone, Debug)]
+pub struct TcpConnector {
+    pub(crate) addrs: Vec<SocketAddr>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) write_timeout: Option<Duration>,
+}
+
+impl TcpConnector {
+    pub fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            addrs: addr.to_socket_addrs()?.collect(),
+            connect_timeout: None,
+            write_timeout: None,
+        })
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Fail writes the peer does not accept within `timeout` instead of blocking indefinitely
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+}
+
+impl Connector for TcpConnector {
+    type Stream = TcpStream;
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let stream = match self.connect_timeout {
+            None => TcpStream::connect(&self.addrs[..])?,
+            Some(timeout) => connect_timeout(&self.addrs, timeout)?,
+        };
+        stream.set_write_timeout(self.write_timeout)?;
+
+        Ok(stream)
+    }
+}
+
+fn connect_timeout(addrs: &[SocketAddr], timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = None;
+
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no socket address to connect to",
+        )
+    }))
+}
+
+/// Connects to a Unix domain socket listener, e.g. Telegraf's `socket_listener` with `unix://`
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct UnixConnector {
+    pub(crate) path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixConnector {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(unix)]
+impl Connector for UnixConnector {
+    type Stream = UnixStream;
+
+    fn connect(&self) -> io::Result<UnixStream> {
+        UnixStream::connect(&self.path)
+    }
+}
+
+/// Streams newline terminated line protocol over a persistent connection
+///
+/// Every write is flushed before it returns, nothing is held back between writes, the buffer only
+/// coalesces the socket writes of one call. Flushing blocks while the peer does not keep up, at
+/// most for the connector's write timeout. If writing fails the connection is re-established and
+/// the batch is sent once more, so points may be delivered twice when the failure happened after
+/// the peer already received them.
+pub struct StreamWriter<C: Connector> {
+    connector: C,
+    stream: Option<BufWriter<C::Stream>>,
+    buffer_capacity: usize,
+}
+
+pub type TcpWriter = StreamWriter<TcpConnector>;
+#[cfg(unix)]
+pub type UnixWriter = StreamWriter<UnixConnector>;
+
+impl<C: Connector> StreamWriter<C> {
+    /// Create the writer, the connection is established lazily on the first write
+    pub fn new(connector: C) -> Self {
+        Self {
+            connector,
+            stream: None,
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+        }
+    }
+
+    pub fn with_buffer_capacity(mut self, buffer_capacity: usize) -> Self {
+        self.buffer_capacity = buffer_capacity;
+        self
+    }
+
+    pub fn write_single(&mut self, point: DataPoint) -> anyhow::Result<()> {
+        self.write(vec![point])
+    }
+
+    /// Write point with specified precision
+    pub fn write_single_with_precision(
+        &mut self,
+        point: DataPoint,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(vec![point], precision)
+    }
+
+    /// Write points with default precision
+    pub fn write(&mut self, points: impl IntoIterator<Item = DataPoint>) -> anyhow::Result<()> {
+        self.write_with_precision(points, WritePrecision::default())
+    }
+
+    /// Write points with specified precision
+    pub fn write_with_precision(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        let mut body = points.to_line_protocol(precision)?;
+        if body.is_empty() {
+            return Ok(());
+        }
+        body.push('\n');
+
+        if let Err(e) = self.send(body.as_bytes()) {
+            debug!("Writing to stream failed, reconnecting: {e}");
+
+            self.send(body.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self.stream.insert(BufWriter::with_capacity(
+                self.buffer_capacity,
+                self.connector.connect()?,
+            )),
+        };
+
+        let result = stream.write_all(buf).and_then(|_| stream.flush());
+        if result.is_err() {
+            // the buffered remainder belongs to a broken connection, discard it with the stream
+            if let Some(stream) = self.stream.take() {
+                let _ = stream.into_parts();
+            }
+        }
+
+        result
+    }
+}
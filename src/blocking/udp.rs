@@ -0,0 +1,83 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::influx::to_line_protocol_chunks;
+use crate::{DataPoint, WritePrecision};
+
+/// Default maximum datagram payload, small enough to avoid fragmentation on typical networks
+pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1400;
+
+/// Fire-and-forget line protocol writer for InfluxDB 1.x / Telegraf UDP listeners
+pub struct UdpWriter {
+    socket: UdpSocket,
+    max_datagram_size: usize,
+}
+
+impl UdpWriter {
+    /// Bind an ephemeral local socket and connect it to `addr`
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no socket address to connect to",
+            )
+        })?;
+
+        let socket = UdpSocket::bind(unspecified_addr(&addr))?;
+        socket.connect(addr)?;
+
+        Ok(Self::from_socket(socket))
+    }
+
+    /// Use an already connected socket
+    pub fn from_socket(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        }
+    }
+
+    /// Set the maximum payload per datagram, points are packed into datagrams up to this size
+    pub fn with_max_datagram_size(mut self, max_datagram_size: usize) -> Self {
+        self.max_datagram_size = max_datagram_size;
+        self
+    }
+
+    pub fn write_single(&mut self, point: DataPoint) -> anyhow::Result<()> {
+        self.write(vec![point])
+    }
+
+    /// Write point with specified precision
+    pub fn write_single_with_precision(
+        &mut self,
+        point: DataPoint,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        self.write_with_precision(vec![point], precision)
+    }
+
+    /// Write points with default precision
+    pub fn write(&mut self, points: impl IntoIterator<Item = DataPoint>) -> anyhow::Result<()> {
+        self.write_with_precision(points, WritePrecision::default())
+    }
+
+    /// Write points with specified precision
+    pub fn write_with_precision(
+        &mut self,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<()> {
+        for datagram in to_line_protocol_chunks(points, precision, self.max_datagram_size)? {
+            self.socket.send(datagram.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn unspecified_addr(remote: &SocketAddr) -> SocketAddr {
+    match remote {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::Read;
+use std::time::Duration;
+
+use ureq::Agent;
+use url::Url;
+
+use crate::blocking::BlockingClient;
+use crate::buckets::BucketsClient;
+use crate::{Authorization, InfluxWriter};
+
+/// [BlockingClient] backed by a reused `ureq` [Agent], no async runtime required
+#[derive(Clone)]
+pub struct UreqClient {
+    agent: Agent,
+}
+
+impl UreqClient {
+    pub(crate) fn new() -> Self {
+        Self::from_agent(agent(None))
+    }
+
+    /// Create a client whose requests fail if they take longer than `timeout` in total
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::from_agent(agent(Some(timeout)))
+    }
+
+    /// Use an already configured agent
+    ///
+    /// Unsuccessful status codes are reported by [InfluxWriter], so the agent should be built with
+    /// `http_status_as_error(false)` to preserve the server's error message.
+    pub fn from_agent(agent: Agent) -> Self {
+        Self { agent }
+    }
+}
+
+impl BlockingClient for UreqClient {
+    fn execute(&mut self, req: http::Request<String>) -> anyhow::Result<http::Response<Vec<u8>>> {
+        let response = self.agent.run(req)?;
+        let (parts, mut body) = response.into_parts();
+
+        // `read_to_vec` on the body itself stops at 10 MB, query results can be larger
+        let body = body.with_config().limit(u64::MAX).read_to_vec()?;
+
+        Ok(http::Response::from_parts(parts, body))
+    }
+
+    fn execute_streaming(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<http::Response<Box<dyn Read + Send>>> {
+        let response = self.agent.run(req)?;
+
+        Ok(response.map(|body| Box::new(body.into_reader()) as Box<dyn Read + Send>))
+    }
+}
+
+impl InfluxWriter<UreqClient> {
+    pub fn new(
+        url: Url,
+        authorization: Authorization,
+        org: impl Into<String>,
+        bucket: impl Into<String>,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_blocking_client(UreqClient::new(), url, authorization, org, bucket)
+    }
+}
+
+impl BucketsClient<UreqClient> {
+    pub fn new(url: Url, authorization: Authorization) -> Self {
+        Self::new_with_blocking_client(UreqClient::new(), url, authorization)
+    }
+}
+
+fn agent(timeout: Option<Duration>) -> Agent {
+    Agent::new_with_config(
+        Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(timeout)
+            .build(),
+    )
+}
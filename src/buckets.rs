@@ -0,0 +1,498 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use http::{Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use url::Url;
+
+use crate::blocking::BlockingClient;
+use crate::{
+    api_request, error_for_status, url_request, AsyncClient, Authorization, InfluxWriter,
+    WritePrecision, EMPTY_WRITE_MESSAGE,
+};
+
+pub const API_ENDPOINT_BUCKETS: &str = "/api/v2/buckets";
+pub const API_ENDPOINT_ORGS: &str = "/api/v2/orgs";
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Bucket {
+    pub id: String,
+    #[serde(rename = "orgID")]
+    pub org_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub retention_rules: Vec<RetentionRule>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Data older than `every_seconds` is deleted, `0` keeps data forever
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionRule {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub every_seconds: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_group_duration_seconds: Option<u64>,
+}
+
+impl RetentionRule {
+    pub fn expire(retention: Duration) -> Self {
+        Self {
+            kind: "expire".to_owned(),
+            every_seconds: retention.as_secs(),
+            shard_group_duration_seconds: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+}
+
+/// Body of `POST /api/v2/buckets`
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateBucketRequest {
+    #[serde(rename = "orgID")]
+    pub org_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub retention_rules: Vec<RetentionRule>,
+}
+
+impl CreateBucketRequest {
+    /// Request a bucket that keeps data forever
+    pub fn new(org_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            org_id: org_id.into(),
+            name: name.into(),
+            description: None,
+            retention_rules: vec![RetentionRule::expire(Duration::ZERO)],
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention_rules = vec![RetentionRule::expire(retention)];
+        self
+    }
+}
+
+/// Body of `PATCH /api/v2/buckets/{id}`, only set fields are changed
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateBucketRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_rules: Option<Vec<RetentionRule>>,
+}
+
+impl UpdateBucketRequest {
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention_rules = Some(vec![RetentionRule::expire(retention)]);
+        self
+    }
+}
+
+/// Filters of `GET /api/v2/buckets`, unset filters match every bucket
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListBucketsRequest {
+    pub org: Option<String>,
+    pub org_id: Option<String>,
+    pub name: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl ListBucketsRequest {
+    pub fn with_org(mut self, org: impl Into<String>) -> Self {
+        self.org = Some(org.into());
+        self
+    }
+
+    pub fn with_org_id(mut self, org_id: impl Into<String>) -> Self {
+        self.org_id = Some(org_id.into());
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        [
+            ("org", self.org.clone()),
+            ("orgID", self.org_id.clone()),
+            ("name", self.name.clone()),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+            ("offset", self.offset.map(|offset| offset.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| Some((k, v?)))
+        .collect()
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Buckets {
+    pub(crate) buckets: Vec<Bucket>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Organizations {
+    pub(crate) orgs: Vec<Organization>,
+}
+
+/// Error body of the InfluxDB 2 api
+#[derive(Deserialize)]
+struct ApiError {
+    code: String,
+    message: String,
+}
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error("Bucket {bucket:?} does not exist in org {org:?} or the token may not read it")]
+    BucketNotFound { org: String, bucket: String },
+    #[error("Org {0:?} does not exist or the token may not read it")]
+    OrgNotFound(String),
+    #[error("Token may not write to bucket {bucket:?} ({status}): {message}")]
+    WritePermissionDenied {
+        bucket: String,
+        status: StatusCode,
+        message: String,
+    },
+}
+
+impl<W> InfluxWriter<W> {
+    pub(crate) fn build_find_bucket_request(&self) -> anyhow::Result<Request<String>> {
+        self.build_api_request(
+            Method::GET,
+            API_ENDPOINT_BUCKETS,
+            &[("name", &self.bucket), ("org", &self.org)],
+            String::new(),
+        )
+    }
+
+    /// Interpret the bucket lookup, returns whether the bucket has to be created
+    ///
+    /// `create` decides whether a missing bucket is an error.
+    pub(crate) fn check_find_bucket_response(
+        &self,
+        response: Response<Vec<u8>>,
+        create: bool,
+    ) -> anyhow::Result<bool> {
+        let missing = match response.status() {
+            StatusCode::NOT_FOUND => true,
+            _ => {
+                let buckets: Buckets = serde_json::from_slice(error_for_status(response)?.body())?;
+                !buckets.buckets.iter().any(|b| b.name == self.bucket)
+            }
+        };
+
+        if missing && !create {
+            return Err(VerifyError::BucketNotFound {
+                org: self.org.clone(),
+                bucket: self.bucket.clone(),
+            }
+            .into());
+        }
+
+        Ok(missing)
+    }
+
+    pub(crate) fn build_find_org_request(&self) -> anyhow::Result<Request<String>> {
+        self.build_api_request(
+            Method::GET,
+            API_ENDPOINT_ORGS,
+            &[("org", &self.org)],
+            String::new(),
+        )
+    }
+
+    /// Build the request creating the bucket in the org returned by the org lookup
+    pub(crate) fn build_create_bucket_request(
+        &self,
+        find_org_response: Response<Vec<u8>>,
+        retention: Option<Duration>,
+    ) -> anyhow::Result<Request<String>> {
+        let org = find_org(find_org_response, &self.org)?;
+
+        let body = serde_json::to_string(
+            &CreateBucketRequest::new(&org.id, &self.bucket)
+                .with_retention(retention.unwrap_or_default()),
+        )?;
+
+        self.build_api_request(Method::POST, API_ENDPOINT_BUCKETS, &[], body)
+    }
+
+    /// Write without points, InfluxDB checks the permissions before it rejects the empty body
+    pub(crate) fn build_write_permission_request(&self) -> anyhow::Result<Request<String>> {
+        self.build_write_request(String::new(), WritePrecision::default())
+    }
+
+    pub(crate) fn check_write_permission_response(
+        &self,
+        response: Response<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::BAD_REQUEST if is_empty_write_rejection(response.body()) => Ok(()),
+            StatusCode::NOT_FOUND => Err(VerifyError::BucketNotFound {
+                org: self.org.clone(),
+                bucket: self.bucket.clone(),
+            }
+            .into()),
+            status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                Err(VerifyError::WritePermissionDenied {
+                    bucket: self.bucket.clone(),
+                    status,
+                    message: String::from_utf8_lossy(response.body()).into_owned(),
+                }
+                .into())
+            }
+            _ => error_for_status(response).map(|_| ()),
+        }
+    }
+}
+
+/// Management client for buckets, using the same transports as [InfluxWriter]
+///
+/// Async and blocking clients are supported, blocking methods carry a `_blocking` suffix.
+pub struct BucketsClient<W> {
+    client: W,
+    url: Url,
+    authorization: Authorization,
+}
+
+impl<W> BucketsClient<W> {
+    fn build_request(
+        &self,
+        method: Method,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        body: String,
+    ) -> anyhow::Result<Request<String>> {
+        api_request(
+            &self.url,
+            &self.authorization,
+            method,
+            endpoint,
+            query,
+            body,
+        )
+    }
+
+    fn build_create_request(
+        &self,
+        bucket: &CreateBucketRequest,
+    ) -> anyhow::Result<Request<String>> {
+        let body = serde_json::to_string(bucket)?;
+        self.build_request(Method::POST, API_ENDPOINT_BUCKETS, &[], body)
+    }
+
+    fn build_list_request(&self, filter: &ListBucketsRequest) -> anyhow::Result<Request<String>> {
+        let query = filter.query();
+        let query: Vec<(&str, &str)> = query.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+        self.build_request(Method::GET, API_ENDPOINT_BUCKETS, &query, String::new())
+    }
+
+    /// Request for the bucket `id`, which is encoded as a single path segment
+    fn build_bucket_request(
+        &self,
+        method: Method,
+        id: &str,
+        body: String,
+    ) -> anyhow::Result<Request<String>> {
+        // `.` and `..` would be resolved as relative segments
+        if matches!(id, "" | "." | "..") {
+            bail!("Invalid bucket id {id:?}");
+        }
+
+        let mut url = self.url.join(API_ENDPOINT_BUCKETS)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Url {} can not have path segments", self.url))?
+            .push(id);
+
+        url_request(url, &self.authorization, method, &[], body)
+    }
+
+    fn build_get_request(&self, id: &str) -> anyhow::Result<Request<String>> {
+        self.build_bucket_request(Method::GET, id, String::new())
+    }
+
+    fn build_update_request(
+        &self,
+        id: &str,
+        update: &UpdateBucketRequest,
+    ) -> anyhow::Result<Request<String>> {
+        let body = serde_json::to_string(update)?;
+        self.build_bucket_request(Method::PATCH, id, body)
+    }
+
+    fn build_delete_request(&self, id: &str) -> anyhow::Result<Request<String>> {
+        self.build_bucket_request(Method::DELETE, id, String::new())
+    }
+
+    fn build_find_org_request(&self, org: &str) -> anyhow::Result<Request<String>> {
+        self.build_request(
+            Method::GET,
+            API_ENDPOINT_ORGS,
+            &[("org", org)],
+            String::new(),
+        )
+    }
+}
+
+impl<W: AsyncClient> BucketsClient<W> {
+    pub fn new_with_client(client: W, url: Url, authorization: Authorization) -> Self {
+        Self {
+            client,
+            url,
+            authorization,
+        }
+    }
+
+    pub async fn create(&mut self, bucket: &CreateBucketRequest) -> anyhow::Result<Bucket> {
+        let req = self.build_create_request(bucket)?;
+        parse_json(self.client.execute(req).await?)
+    }
+
+    pub async fn list(&mut self, filter: &ListBucketsRequest) -> anyhow::Result<Vec<Bucket>> {
+        let req = self.build_list_request(filter)?;
+        Ok(parse_json::<Buckets>(self.client.execute(req).await?)?.buckets)
+    }
+
+    pub async fn get(&mut self, id: &str) -> anyhow::Result<Bucket> {
+        let req = self.build_get_request(id)?;
+        parse_json(self.client.execute(req).await?)
+    }
+
+    pub async fn update(
+        &mut self,
+        id: &str,
+        update: &UpdateBucketRequest,
+    ) -> anyhow::Result<Bucket> {
+        let req = self.build_update_request(id, update)?;
+        parse_json(self.client.execute(req).await?)
+    }
+
+    pub async fn delete(&mut self, id: &str) -> anyhow::Result<()> {
+        let req = self.build_delete_request(id)?;
+        error_for_status(self.client.execute(req).await?).map(|_| ())
+    }
+
+    /// Look up an org by name, e.g. to get the id required by [CreateBucketRequest]
+    pub async fn find_org(&mut self, org: &str) -> anyhow::Result<Organization> {
+        let req = self.build_find_org_request(org)?;
+        find_org(self.client.execute(req).await?, org)
+    }
+}
+
+impl<W: BlockingClient> BucketsClient<W> {
+    pub fn new_with_blocking_client(client: W, url: Url, authorization: Authorization) -> Self {
+        Self {
+            client,
+            url,
+            authorization,
+        }
+    }
+
+    pub fn create_blocking(&mut self, bucket: &CreateBucketRequest) -> anyhow::Result<Bucket> {
+        let req = self.build_create_request(bucket)?;
+        parse_json(self.client.execute(req)?)
+    }
+
+    pub fn list_blocking(&mut self, filter: &ListBucketsRequest) -> anyhow::Result<Vec<Bucket>> {
+        let req = self.build_list_request(filter)?;
+        Ok(parse_json::<Buckets>(self.client.execute(req)?)?.buckets)
+    }
+
+    pub fn get_blocking(&mut self, id: &str) -> anyhow::Result<Bucket> {
+        let req = self.build_get_request(id)?;
+        parse_json(self.client.execute(req)?)
+    }
+
+    pub fn update_blocking(
+        &mut self,
+        id: &str,
+        update: &UpdateBucketRequest,
+    ) -> anyhow::Result<Bucket> {
+        let req = self.build_update_request(id, update)?;
+        parse_json(self.client.execute(req)?)
+    }
+
+    pub fn delete_blocking(&mut self, id: &str) -> anyhow::Result<()> {
+        let req = self.build_delete_request(id)?;
+        error_for_status(self.client.execute(req)?).map(|_| ())
+    }
+
+    /// Look up an org by name, e.g. to get the id required by [CreateBucketRequest]
+    pub fn find_org_blocking(&mut self, org: &str) -> anyhow::Result<Organization> {
+        let req = self.build_find_org_request(org)?;
+        find_org(self.client.execute(req)?, org)
+    }
+}
+
+/// Whether `body` is the error InfluxDB 2 answers an authorized write without points with
+fn is_empty_write_rejection(body: &[u8]) -> bool {
+    serde_json::from_slice::<ApiError>(body)
+        .is_ok_and(|error| error.code == "invalid" && error.message == EMPTY_WRITE_MESSAGE)
+}
+
+fn parse_json<T: for<'de> Deserialize<'de>>(response: Response<Vec<u8>>) -> anyhow::Result<T> {
+    Ok(serde_json::from_slice(error_for_status(response)?.body())?)
+}
+
+fn find_org(response: Response<Vec<u8>>, org: &str) -> anyhow::Result<Organization> {
+    let orgs: Organizations = match response.status() {
+        StatusCode::NOT_FOUND => Organizations { orgs: Vec::new() },
+        _ => parse_json(response)?,
+    };
+
+    orgs.orgs
+        .into_iter()
+        .find(|o| o.name == org)
+        .ok_or_else(|| VerifyError::OrgNotFound(org.to_owned()).into())
+}
@@ -0,0 +1,651 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use thiserror::Error;
+
+use crate::DataPoint;
+
+/// How distinct series and tag values are counted
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Tracking {
+    /// Remember a hash of every series, memory grows with the limit
+    Exact,
+    /// Estimate with a HyperLogLog sketch of `2^precision` one byte registers per measurement and
+    /// tag, `precision` is clamped to `4..=16`. The error is about `1.04 / sqrt(2^precision)`, so
+    /// the limit is compared against an estimate as well.
+    ///
+    /// A sketch only counts, accepted series are remembered in a Bloom filter of about 10 bits per
+    /// series of the limit (at most 128 MiB per measurement). Past the limit, about 1% of new
+    /// series are mistaken for accepted ones and written anyway.
+    HyperLogLog { precision: u8 },
+}
+
+/// What happens to a point that would create a series past the limit
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OverflowAction {
+    /// Fail the whole write with [CardinalityError::LimitExceeded]
+    Error,
+    /// Silently leave the point out of the batch
+    Drop,
+    /// Replace the value of the tag with the most distinct values by this value
+    ///
+    /// Redirected points are always accepted, so the fallback series may push a measurement
+    /// slightly past the limit.
+    Fallback(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CardinalityError {
+    #[error(
+        "Point {point} ({measurement}) exceeds the limit of {limit} series, \
+         tag with most values: {tag:?}"
+    )]
+    LimitExceeded {
+        point: usize,
+        measurement: String,
+        tag: Option<String>,
+        limit: u64,
+    },
+}
+
+/// Reported once per measurement when it first reaches the limit
+#[derive(Clone, Debug, PartialEq)]
+pub struct CardinalityEvent {
+    pub measurement: String,
+    /// The tag with the most distinct values, most likely the one causing the growth
+    pub tag: Option<String>,
+    pub tag_values: u64,
+    pub series: u64,
+    pub limit: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CardinalityMetrics {
+    /// Tracked series per measurement, estimated when using HyperLogLog
+    pub series: HashMap<String, u64>,
+    /// Points left out by [OverflowAction::Drop]
+    pub dropped: u64,
+    /// Points whose tag was replaced by [OverflowAction::Fallback]
+    pub redirected: u64,
+    /// Points that failed a write with [OverflowAction::Error]
+    pub rejected: u64,
+}
+
+type Callback = Arc<dyn Fn(&CardinalityEvent) + Send + Sync>;
+
+/// Limits the number of distinct series, i.e. tag value combinations, per measurement
+///
+/// All clones share the tracked series, so a clone can be handed to
+/// [crate::InfluxWriter::with_cardinality_guard] while another one reads the metrics. Series that
+/// were already written are always accepted, only new series past the limit are handled by the
+/// [OverflowAction]. The series of a batch are only tracked if the whole batch is accepted, a
+/// batch rejected by [OverflowAction::Error] is only counted in the metrics.
+#[derive(Clone)]
+pub struct CardinalityGuard {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    limit: u64,
+    tracking: Tracking,
+    action: OverflowAction,
+    callback: Option<Callback>,
+    measurements: HashMap<String, MeasurementState>,
+    dropped: u64,
+    redirected: u64,
+    rejected: u64,
+}
+
+struct MeasurementState {
+    series: SeriesSet,
+    tags: HashMap<String, Counter>,
+    reported: bool,
+}
+
+/// Series and tag values of a checked batch, tracked once it is committed
+#[derive(Default)]
+pub(crate) struct StagedBatch {
+    measurements: HashMap<String, StagedMeasurement>,
+    dropped: u64,
+    redirected: u64,
+}
+
+/// New series and all tag values of one measurement in a batch
+#[derive(Default)]
+struct StagedMeasurement {
+    series: HashSet<u64>,
+    tags: HashMap<String, HashSet<u64>>,
+}
+
+impl CardinalityGuard {
+    /// Allow at most `limit` series per measurement, tracked exactly, failing writes past it
+    pub fn new(limit: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                limit,
+                tracking: Tracking::Exact,
+                action: OverflowAction::Error,
+                callback: None,
+                measurements: HashMap::new(),
+                dropped: 0,
+                redirected: 0,
+                rejected: 0,
+            })),
+        }
+    }
+
+    pub fn with_tracking(self, tracking: Tracking) -> Self {
+        self.state().tracking = tracking;
+        self
+    }
+
+    pub fn with_action(self, action: OverflowAction) -> Self {
+        self.state().action = action;
+        self
+    }
+
+    /// Call `callback` the first time a measurement reaches the limit, e.g. to raise an alert
+    pub fn on_limit(self, callback: impl Fn(&CardinalityEvent) + Send + Sync + 'static) -> Self {
+        self.state().callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn metrics(&self) -> CardinalityMetrics {
+        let state = self.state();
+
+        CardinalityMetrics {
+            series: state
+                .measurements
+                .iter()
+                .map(|(name, measurement)| (name.clone(), measurement.series.count()))
+                .collect(),
+            dropped: state.dropped,
+            redirected: state.redirected,
+            rejected: state.rejected,
+        }
+    }
+
+    /// Forget all tracked series and reset the metrics
+    pub fn reset(&self) {
+        let mut state = self.state();
+        state.measurements.clear();
+        state.dropped = 0;
+        state.redirected = 0;
+        state.rejected = 0;
+    }
+
+    /// Track the series of `points`, returning the points to write
+    pub fn check(&self, points: Vec<DataPoint>) -> Result<Vec<DataPoint>, CardinalityError> {
+        let (points, batch) = self.stage(points)?;
+        self.commit(batch);
+
+        Ok(points)
+    }
+
+    /// Like [CardinalityGuard::check], but the series are only tracked once the batch is
+    /// committed with [CardinalityGuard::commit]
+    pub(crate) fn stage(
+        &self,
+        points: Vec<DataPoint>,
+    ) -> Result<(Vec<DataPoint>, StagedBatch), CardinalityError> {
+        let mut events = Vec::new();
+        let (result, callback) = {
+            let mut state = self.state();
+            (state.stage(points, &mut events), state.callback.clone())
+        };
+
+        // called without holding the lock, so the callback may use the guard
+        if let Some(callback) = callback {
+            events.iter().for_each(|event| callback(event));
+        }
+
+        result
+    }
+
+    pub(crate) fn commit(&self, batch: StagedBatch) {
+        self.state().commit(batch);
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn stage(
+        &mut self,
+        points: Vec<DataPoint>,
+        events: &mut Vec<CardinalityEvent>,
+    ) -> Result<(Vec<DataPoint>, StagedBatch), CardinalityError> {
+        let (limit, tracking) = (self.limit, self.tracking);
+        let mut batch = StagedBatch::default();
+        let mut accepted = Vec::with_capacity(points.len());
+
+        for (index, mut point) in points.into_iter().enumerate() {
+            let committed = self.measurements.get(point.measurement());
+            let staged = batch
+                .measurements
+                .entry(point.measurement().to_owned())
+                .or_default();
+
+            let series = series_hash(&point);
+            let count = committed.map_or(0, |m| m.series.count()) + staged.series.len() as u64;
+            if is_known(committed, staged, series) || count < limit {
+                staged.track(committed, &point, series);
+                accepted.push(point);
+                continue;
+            }
+
+            let tag = exploding_tag(committed, staged, &point);
+            let measurement = self
+                .measurements
+                .entry(point.measurement().to_owned())
+                .or_insert_with(|| MeasurementState::new(tracking, limit));
+            if !measurement.reported {
+                measurement.reported = true;
+                events.push(CardinalityEvent {
+                    measurement: point.measurement().to_owned(),
+                    tag: tag.as_ref().map(|(tag, _)| tag.clone()),
+                    tag_values: tag.as_ref().map_or(0, |(_, values)| *values),
+                    series: count,
+                    limit,
+                });
+            }
+
+            match (&self.action, tag) {
+                (OverflowAction::Error, tag) => {
+                    self.rejected += 1;
+                    return Err(CardinalityError::LimitExceeded {
+                        point: index,
+                        measurement: point.measurement().to_owned(),
+                        tag: tag.map(|(tag, _)| tag),
+                        limit,
+                    });
+                }
+                (OverflowAction::Fallback(fallback), Some((tag, _))) => {
+                    point.set_tag(tag, fallback.clone());
+                    let series = series_hash(&point);
+                    staged.track(self.measurements.get(point.measurement()), &point, series);
+                    batch.redirected += 1;
+                    accepted.push(point);
+                }
+                (OverflowAction::Drop | OverflowAction::Fallback(_), _) => batch.dropped += 1,
+            }
+        }
+
+        Ok((accepted, batch))
+    }
+
+    fn commit(&mut self, batch: StagedBatch) {
+        let (limit, tracking) = (self.limit, self.tracking);
+
+        for (name, staged) in batch.measurements {
+            let measurement = self
+                .measurements
+                .entry(name)
+                .or_insert_with(|| MeasurementState::new(tracking, limit));
+
+            staged
+                .series
+                .into_iter()
+                .for_each(|series| measurement.series.insert(series));
+            for (tag, values) in staged.tags {
+                let counter = measurement
+                    .tags
+                    .entry(tag)
+                    .or_insert_with(|| Counter::new(tracking));
+                values.into_iter().for_each(|value| counter.insert(value));
+            }
+        }
+
+        self.dropped += batch.dropped;
+        self.redirected += batch.redirected;
+    }
+}
+
+impl MeasurementState {
+    fn new(tracking: Tracking, limit: u64) -> Self {
+        Self {
+            series: SeriesSet::new(tracking, limit),
+            tags: HashMap::new(),
+            reported: false,
+        }
+    }
+}
+
+impl StagedMeasurement {
+    fn track(&mut self, committed: Option<&MeasurementState>, point: &DataPoint, series: u64) {
+        if !committed.is_some_and(|m| m.series.contains(series)) {
+            self.series.insert(series);
+        }
+        for (tag, value) in point.tags() {
+            self.tags
+                .entry(tag.to_owned())
+                .or_default()
+                .insert(hash(value));
+        }
+    }
+}
+
+fn is_known(committed: Option<&MeasurementState>, staged: &StagedMeasurement, series: u64) -> bool {
+    committed.is_some_and(|m| m.series.contains(series)) || staged.series.contains(&series)
+}
+
+/// The tag of `point` with the most distinct values so far, including the staged batch
+fn exploding_tag(
+    committed: Option<&MeasurementState>,
+    staged: &StagedMeasurement,
+    point: &DataPoint,
+) -> Option<(String, u64)> {
+    let no_values = HashSet::new();
+
+    point
+        .tags()
+        .map(|(tag, _)| {
+            let batch = staged.tags.get(tag).unwrap_or(&no_values);
+            let values = match committed.and_then(|m| m.tags.get(tag)) {
+                Some(counter) => counter.count_with(batch),
+                None => batch.len() as u64,
+            };
+            (tag.to_owned(), values)
+        })
+        .max_by_key(|(_, values)| *values)
+}
+
+/// Hash of the tag set, independent of the tag order
+fn series_hash(point: &DataPoint) -> u64 {
+    let mut tags: Vec<(&str, &str)> = point.tags().collect();
+    tags.sort_unstable();
+    hash(&tags)
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+enum Counter {
+    Exact(HashSet<u64>),
+    HyperLogLog(HyperLogLog),
+}
+
+impl Counter {
+    fn new(tracking: Tracking) -> Self {
+        match tracking {
+            Tracking::Exact => Counter::Exact(HashSet::new()),
+            Tracking::HyperLogLog { precision } => {
+                Counter::HyperLogLog(HyperLogLog::new(precision))
+            }
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        match self {
+            Counter::Exact(set) => {
+                set.insert(hash);
+            }
+            Counter::HyperLogLog(sketch) => sketch.insert(hash),
+        }
+    }
+
+    /// The count if `hashes` were inserted as well, a sketch counts all of them as new
+    fn count_with(&self, hashes: &HashSet<u64>) -> u64 {
+        match self {
+            Counter::Exact(set) => set.len() as u64 + hashes.difference(set).count() as u64,
+            Counter::HyperLogLog(sketch) => sketch.count() + hashes.len() as u64,
+        }
+    }
+}
+
+/// The series of a measurement, answering whether a series was accepted before
+enum SeriesSet {
+    Exact(HashSet<u64>),
+    Estimated {
+        sketch: HyperLogLog,
+        accepted: BloomFilter,
+    },
+}
+
+impl SeriesSet {
+    fn new(tracking: Tracking, limit: u64) -> Self {
+        match tracking {
+            Tracking::Exact => SeriesSet::Exact(HashSet::new()),
+            Tracking::HyperLogLog { precision } => SeriesSet::Estimated {
+                sketch: HyperLogLog::new(precision),
+                accepted: BloomFilter::new(limit),
+            },
+        }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        match self {
+            SeriesSet::Exact(set) => set.contains(&hash),
+            SeriesSet::Estimated { accepted, .. } => accepted.contains(hash),
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        match self {
+            SeriesSet::Exact(set) => {
+                set.insert(hash);
+            }
+            SeriesSet::Estimated { sketch, accepted } => {
+                sketch.insert(hash);
+                accepted.insert(hash);
+            }
+        }
+    }
+
+    fn count(&self) -> u64 {
+        match self {
+            SeriesSet::Exact(set) => set.len() as u64,
+            SeriesSet::Estimated { sketch, .. } => sketch.count(),
+        }
+    }
+}
+
+/// Set membership with false positives but no false negatives
+struct BloomFilter {
+    bits: Vec<u64>,
+    len: u64,
+}
+
+impl BloomFilter {
+    /// Hashes per item, with 10 bits per item this gives about 1% false positives
+    const HASHES: u64 = 7;
+    /// 128 MiB, past that the false positive rate grows with the number of items
+    const MAX_BITS: u64 = 1 << 30;
+
+    fn new(capacity: u64) -> Self {
+        let len = capacity.saturating_mul(10).clamp(64, Self::MAX_BITS);
+
+        Self {
+            bits: vec![0; len.div_ceil(64) as usize],
+            len,
+        }
+    }
+
+    /// Bit indices of `hash`, derived from two hashes as in Kirsch and Mitzenmacher
+    fn indices(&self, hash: u64) -> impl Iterator<Item = u64> {
+        let (first, second, len) = (hash, self::hash(hash) | 1, self.len);
+
+        (0..Self::HASHES).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % len)
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.indices(hash)
+            .all(|index| self.bits[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for index in self.indices(hash) {
+            self.bits[(index / 64) as usize] |= 1 << (index % 64);
+        }
+    }
+}
+
+struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new(precision: u8) -> Self {
+        let precision = precision.clamp(4, 16);
+
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    /// Register index and rank of the first set bit in the remaining bits
+    fn position(&self, hash: u64) -> (usize, u8) {
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+
+        (index, rest.leading_zeros() as u8 + 1)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let (index, rank) = self.position(hash);
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn count(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1. + 1.079 / m),
+        };
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&register| 2f64.powi(-i32::from(register)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::cardinality::{
+        CardinalityError, CardinalityGuard, HyperLogLog, OverflowAction, Tracking,
+    };
+    use crate::{DataPoint, DataPointBuilder};
+
+    fn request(id: usize) -> DataPoint {
+        DataPointBuilder::new("http")
+            .with_tag("host", "a")
+            .with_tag("request_id", id.to_string())
+            .with_field("duration", 0.5)
+            .into()
+    }
+
+    #[test]
+    fn error_past_limit() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let reported = events.clone();
+        let guard = CardinalityGuard::new(3)
+            .on_limit(move |event| reported.lock().unwrap().push(event.clone()));
+
+        assert_eq!(3, guard.check((0..3).map(request).collect()).unwrap().len());
+        // known series are still accepted
+        guard.check(vec![request(0)]).unwrap();
+
+        assert_eq!(
+            Err(CardinalityError::LimitExceeded {
+                point: 1,
+                measurement: "http".to_owned(),
+                tag: Some("request_id".to_owned()),
+                limit: 3,
+            }),
+            guard.check(vec![request(1), request(3)])
+        );
+        assert!(guard.check(vec![request(4)]).is_err());
+
+        let events = events.lock().unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Some("request_id"), events[0].tag.as_deref());
+        assert_eq!(3, events[0].tag_values);
+        assert_eq!(2, guard.metrics().rejected);
+    }
+
+    #[test]
+    fn drop_and_fallback() {
+        let guard = CardinalityGuard::new(2).with_action(OverflowAction::Drop);
+        assert_eq!(2, guard.check((0..5).map(request).collect()).unwrap().len());
+        assert_eq!(3, guard.metrics().dropped);
+
+        let guard =
+            CardinalityGuard::new(2).with_action(OverflowAction::Fallback("other".to_owned()));
+        let points = guard.check((0..5).map(request).collect()).unwrap();
+        assert_eq!(5, points.len());
+        assert_eq!(Some("other"), points[4].tag("request_id"));
+        assert_eq!(Some("a"), points[4].tag("host"));
+
+        let metrics = guard.metrics();
+        assert_eq!(3, metrics.redirected);
+        assert_eq!(Some(&3), metrics.series.get("http"));
+    }
+
+    #[test]
+    fn hyperloglog_estimate() {
+        let mut sketch = HyperLogLog::new(12);
+        for i in 0..100_000u64 {
+            sketch.insert(super::hash(i));
+        }
+
+        let estimate = sketch.count() as f64;
+        assert!((estimate - 100_000.).abs() < 5_000., "estimate {estimate}");
+
+        let guard = CardinalityGuard::new(100)
+            .with_tracking(Tracking::HyperLogLog { precision: 12 })
+            .with_action(OverflowAction::Drop);
+        let accepted = guard.check((0..1000).map(request).collect()).unwrap();
+        // the limit is compared against an estimate, and the filter lets a few new series pass
+        assert!((100..=130).contains(&accepted.len()), "{}", accepted.len());
+    }
+
+    #[test]
+    fn hyperloglog_limit_beyond_registers() {
+        // 16 registers saturate long before the limit, membership must not depend on them
+        let guard = CardinalityGuard::new(1000)
+            .with_tracking(Tracking::HyperLogLog { precision: 4 })
+            .with_action(OverflowAction::Drop);
+
+        let accepted = guard.check((0..10_000).map(request).collect()).unwrap();
+        assert!((500..=1500).contains(&accepted.len()), "{}", accepted.len());
+        assert_eq!(accepted.len(), guard.check(accepted.clone()).unwrap().len());
+
+        let new = guard
+            .check((10_000..20_000).map(request).collect())
+            .unwrap();
+        assert!(new.len() < 200, "{}", new.len());
+    }
+
+    #[test]
+    fn rejected_batch_untracked() {
+        let guard = CardinalityGuard::new(3);
+        guard.check((0..2).map(request).collect()).unwrap();
+
+        assert!(guard.check(vec![request(2), request(3)]).is_err());
+        assert_eq!(Some(&2), guard.metrics().series.get("http"));
+        assert_eq!(1, guard.check(vec![request(3)]).unwrap().len());
+    }
+}
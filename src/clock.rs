@@ -0,0 +1,111 @@
+use std::time::Instant;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::Timestamp;
+
+/// Source of the time used to stamp points written without a timestamp
+///
+/// Implemented for closures, so tests can pin the time:
+/// ```
+/// # use chrono::DateTime;
+/// # use influx_write::Timestamp;
+/// # use influx_write::clock::Clock;
+/// let clock = || Timestamp::from(DateTime::from_timestamp(1630424257, 0).unwrap());
+/// assert_eq!(clock(), clock.now());
+/// ```
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Timestamp;
+}
+
+/// The system wall clock
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Utc::now().into()
+    }
+}
+
+/// Converts [Instant]s into timestamps relative to one anchor reading of the wall clock
+///
+/// Readings taken with [Instant::now] keep their exact spacing, even if the system clock is
+/// adjusted in between.
+#[derive(Copy, Clone, Debug)]
+pub struct MonotonicClock {
+    instant: Instant,
+    time: Timestamp,
+}
+
+impl MonotonicClock {
+    /// Anchor at the current instant and wall clock time
+    pub fn new() -> Self {
+        Self::anchored(Instant::now(), SystemClock.now())
+    }
+
+    /// Anchor `instant` at `time`
+    pub fn anchored(instant: Instant, time: Timestamp) -> Self {
+        Self { instant, time }
+    }
+
+    /// The wall clock time of `instant`, which may lie before the anchor
+    pub fn timestamp(&self, instant: Instant) -> Timestamp {
+        let time = self.time.to_datetime();
+        let delta = |duration| TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX);
+
+        match instant.checked_duration_since(self.instant) {
+            Some(after) => time
+                .checked_add_signed(delta(after))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            None => time
+                .checked_sub_signed(delta(self.instant.duration_since(instant)))
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+        }
+        .into()
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Timestamp {
+        self.timestamp(Instant::now())
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Timestamp + Send + Sync,
+{
+    fn now(&self) -> Timestamp {
+        self()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::clock::MonotonicClock;
+    use crate::Timestamp;
+
+    #[test]
+    fn monotonic_clock() {
+        let instant = Instant::now() + Duration::from_secs(10);
+        let clock = MonotonicClock::anchored(instant, Timestamp::from_nanos(10_000_000_000));
+
+        assert_eq!(
+            Timestamp::from_nanos(10_000_000_500),
+            clock.timestamp(instant + Duration::from_nanos(500))
+        );
+        assert_eq!(
+            Timestamp::from_nanos(7_000_000_000),
+            clock.timestamp(instant - Duration::from_secs(3))
+        );
+    }
+}
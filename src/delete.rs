@@ -0,0 +1,149 @@
+use std::fmt::{Display, Formatter};
+
+use http::{Method, Request};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::influx::Timestamp;
+use crate::InfluxWriter;
+
+pub const API_ENDPOINT_DELETE: &str = "/api/v2/delete";
+
+/// Predicate selecting the series to delete, conditions are combined with `AND`
+///
+/// Values are quoted and escaped. Keys are written as they are, so they may only consist of ASCII
+/// letters, digits and `_`, deleting with any other key fails with [PredicateError::InvalidKey].
+///
+/// ```
+/// # use influx_write::delete::DeletePredicate;
+/// let predicate = DeletePredicate::new()
+///     .measurement("airSensors")
+///     .tag("sensor_id", "TLM\"0201");
+///
+/// assert_eq!(
+///     r#"_measurement="airSensors" AND sensor_id="TLM\"0201""#,
+///     predicate.to_string()
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeletePredicate {
+    conditions: Vec<(String, String)>,
+}
+
+impl DeletePredicate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn measurement(self, measurement: impl Into<String>) -> Self {
+        self.tag("_measurement", measurement)
+    }
+
+    /// Only delete series where `key` equals `value`
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.conditions.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Check that every key can be written without changing the meaning of the predicate
+    pub fn validate(&self) -> Result<(), PredicateError> {
+        match self.conditions.iter().find(|(key, _)| !is_valid_key(key)) {
+            Some((key, _)) => Err(PredicateError::InvalidKey(key.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PredicateError {
+    #[error("Invalid predicate key {0:?}, keys may only contain ASCII letters, digits and _")]
+    InvalidKey(String),
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Display for DeletePredicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (key, value)) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                write!(f, " AND ")?;
+            }
+            write!(
+                f,
+                "{key}=\"{}\"",
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct DeleteRequest {
+    start: String,
+    stop: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    predicate: Option<String>,
+}
+
+impl<W> InfluxWriter<W> {
+    pub(crate) fn build_delete_request(
+        &self,
+        start: Timestamp,
+        stop: Timestamp,
+        predicate: &DeletePredicate,
+    ) -> anyhow::Result<Request<String>> {
+        predicate.validate()?;
+
+        let body = serde_json::to_string(&DeleteRequest {
+            start: start.to_rfc3339(),
+            stop: stop.to_rfc3339(),
+            predicate: (!predicate.is_empty()).then(|| predicate.to_string()),
+        })?;
+
+        self.build_api_request(
+            Method::POST,
+            API_ENDPOINT_DELETE,
+            &[("org", &self.org), ("bucket", &self.bucket)],
+            body,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delete::{DeletePredicate, PredicateError};
+
+    #[test]
+    fn hostile_key() {
+        let predicate = DeletePredicate::new()
+            .measurement("cpu")
+            .tag("host=\"a\" OR _measurement", "b");
+
+        assert_eq!(
+            Err(PredicateError::InvalidKey(
+                "host=\"a\" OR _measurement".to_owned()
+            )),
+            predicate.validate()
+        );
+        assert!(DeletePredicate::new().tag("", "a").validate().is_err());
+        assert!(DeletePredicate::new()
+            .tag("sensor id", "a")
+            .validate()
+            .is_err());
+        assert_eq!(
+            Ok(()),
+            DeletePredicate::new()
+                .measurement("cpu")
+                .tag("sensor_id2", "\" OR \"")
+                .validate()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use http::Response;
+use serde::Deserialize;
+
+use crate::{error_for_status, status_error};
+
+/// Result of `GET /ping`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ping {
+    /// Value of the `X-Influxdb-Version` header
+    pub version: Option<String>,
+    /// Value of the `X-Influxdb-Build` header, e.g. `OSS` or `Cloud`
+    pub build: Option<String>,
+}
+
+/// Result of `GET /health`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Health {
+    pub name: String,
+    pub message: Option<String>,
+    pub status: HealthStatus,
+    pub version: Option<String>,
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub checks: Vec<Health>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Pass,
+    Fail,
+}
+
+impl Health {
+    pub fn is_pass(&self) -> bool {
+        self.status == HealthStatus::Pass
+    }
+}
+
+pub(crate) fn parse_ping(response: Response<Vec<u8>>) -> anyhow::Result<Ping> {
+    let response = error_for_status(response)?;
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+
+    Ok(Ping {
+        version: header("x-influxdb-version"),
+        build: header("x-influxdb-build"),
+    })
+}
+
+/// Unhealthy servers answer with `503` and a `fail` status, which is not an error here
+pub(crate) fn parse_health(response: Response<Vec<u8>>) -> anyhow::Result<Health> {
+    match serde_json::from_slice(response.body()) {
+        Ok(health) => Ok(health),
+        Err(_) if !response.status().is_success() => Err(status_error(response.body())),
+        Err(e) => Err(e.into()),
+    }
+}
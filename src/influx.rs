@@ -1,16 +1,103 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
 
-use crate::{ConversionError, WritePrecision};
+use crate::{ConversionError, ParseError, Rounding, WritePrecision};
 
 // <measurement>[,<tag_key>=<tag_value>[,<tag_key>=<tag_value>]] <field_key>=<field_value>[,<field_key>=<field_value>] [<timestamp>]
 // keys not be starting with underscore
+#[derive(Clone, Debug, PartialEq)]
 pub struct DataPoint {
     measurement: String,
     tags: HashMap<String, String>,
     fields: HashMap<String, Value>,
     time: Option<Timestamp>,
+    rounding: Option<Rounding>,
+}
+
+impl DataPoint {
+    pub fn measurement(&self) -> &str {
+        &self.measurement
+    }
+
+    pub fn set_measurement(&mut self, measurement: impl Into<String>) {
+        self.measurement = measurement.into();
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    /// Tags in no particular order
+    pub fn tags(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Add or replace a tag, returning the previous value
+    pub fn set_tag(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.tags.insert(key.into(), value.into())
+    }
+
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        self.tags.remove(key)
+    }
+
+    pub fn field(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// Fields in no particular order, there is always at least one
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Add or replace a field, returning the previous value
+    pub fn set_field(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.fields.insert(key.into(), value.into())
+    }
+
+    /// Remove a field, removing the last one fails with [ConversionError::MissingField]
+    pub fn remove_field(&mut self, key: &str) -> Result<Option<Value>, ConversionError> {
+        if self.fields.len() == 1 && self.fields.contains_key(key) {
+            return Err(ConversionError::MissingField);
+        }
+
+        Ok(self.fields.remove(key))
+    }
+
+    pub fn time(&self) -> Option<Timestamp> {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: impl Into<Timestamp>) {
+        self.time = Some(time.into());
+    }
+
+    /// Remove the timestamp, the point is then stamped by the writer's clock or the server
+    pub fn clear_time(&mut self) -> Option<Timestamp> {
+        self.time.take()
+    }
+
+    pub(crate) fn time_or_insert(&mut self, time: Timestamp) {
+        self.time.get_or_insert(time);
+    }
+
+    pub(crate) fn rounding_or_insert(&mut self, rounding: Rounding) {
+        self.rounding.get_or_insert(rounding);
+    }
+
+    /// Add the tags the point does not carry itself
+    pub(crate) fn apply_default_tags(&mut self, default_tags: &HashMap<String, String>) {
+        for (key, value) in default_tags {
+            if !self.tags.contains_key(key) {
+                self.tags.insert(key.clone(), value.clone());
+            }
+        }
+    }
 }
 
 pub(crate) trait LineProtocol {
@@ -31,6 +118,69 @@ where
     }
 }
 
+/// Replace [WritePrecision::Auto] with the coarsest precision that keeps all timestamps of `points`
+///
+/// Nanoseconds are only chosen if every timestamp fits, otherwise microseconds are used.
+pub(crate) fn resolve_precision(points: &[DataPoint], precision: WritePrecision) -> WritePrecision {
+    if precision != WritePrecision::Auto {
+        return precision;
+    }
+
+    let times = || points.iter().filter_map(|point| point.time);
+    let resolved = times()
+        .map(Timestamp::lossless_precision)
+        .max_by_key(|precision| match precision {
+            WritePrecision::S => 0,
+            WritePrecision::MS => 1,
+            WritePrecision::US => 2,
+            WritePrecision::NS | WritePrecision::Auto => 3,
+        })
+        .unwrap_or(WritePrecision::S);
+
+    if resolved == WritePrecision::NS
+        && times().any(|time| time.inner.timestamp_nanos_opt().is_none())
+    {
+        WritePrecision::US
+    } else {
+        resolved
+    }
+}
+
+/// Serialize points into newline separated chunks of at most `max_size` bytes
+///
+/// Lines are never split, a single line longer than `max_size` is an error.
+pub(crate) fn to_line_protocol_chunks(
+    points: impl IntoIterator<Item = DataPoint>,
+    precision: WritePrecision,
+    max_size: usize,
+) -> Result<Vec<String>, ConversionError> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+
+    for point in points {
+        let line = point.to_line_protocol(precision)?;
+
+        if line.len() > max_size {
+            return Err(ConversionError::LineTooLong(line.len(), max_size));
+        }
+
+        if !chunk.is_empty() && chunk.len() + 1 + line.len() > max_size {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+
+        if !chunk.is_empty() {
+            chunk.push('\n');
+        }
+        chunk.push_str(&line);
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
 impl LineProtocol for &DataPoint {
     fn to_line_protocol(self, precision: WritePrecision) -> Result<String, ConversionError> {
         debug_assert!(!self.fields.is_empty());
@@ -68,13 +218,153 @@ impl LineProtocol for &DataPoint {
         if let Some(timestamp) = &self.time {
             buf.push(' ');
 
-            buf.push_str(&timestamp.to_line_protocol(precision)?)
+            buf.push_str(&timestamp.to_line_protocol(precision, self.rounding.unwrap_or_default())?)
         }
 
         Ok(buf)
     }
 }
 
+/// Parse a line protocol body back into points, the inverse of [LineProtocol]
+pub(crate) fn parse_line_protocol(
+    body: &str,
+    precision: WritePrecision,
+) -> Result<Vec<DataPoint>, ParseError> {
+    body.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            LineParser::new(line)
+                .parse(precision)
+                .map_err(|reason| ParseError::InvalidLine(i + 1, reason))
+        })
+        .collect()
+}
+
+struct LineParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            chars: line.chars().peekable(),
+        }
+    }
+
+    fn parse(mut self, precision: WritePrecision) -> Result<DataPoint, String> {
+        let measurement = self.read_until(&[',', ' ']);
+        if measurement.is_empty() {
+            return Err("missing measurement".to_owned());
+        }
+
+        let mut tags = HashMap::new();
+        while self.chars.next_if_eq(&',').is_some() {
+            let key = self.read_until(&['=']);
+            self.expect('=')?;
+            tags.insert(key, self.read_until(&[',', ' ']));
+        }
+
+        self.expect(' ')?;
+
+        let mut fields = HashMap::new();
+        loop {
+            let key = self.read_until(&['=']);
+            self.expect('=')?;
+            fields.insert(key, self.read_value()?);
+
+            if self.chars.next_if_eq(&',').is_none() {
+                break;
+            }
+        }
+
+        let time = match self.chars.next() {
+            None => None,
+            Some(' ') => {
+                let timestamp: String = self.chars.by_ref().collect();
+                let timestamp = timestamp
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid timestamp {timestamp}"))?;
+
+                Some(
+                    Timestamp::from_line_protocol(timestamp, precision)
+                        .ok_or_else(|| format!("timestamp {timestamp} out of range"))?,
+                )
+            }
+            Some(c) => return Err(format!("unexpected character {c:?}")),
+        };
+
+        Ok(DataPoint {
+            measurement,
+            tags,
+            fields,
+            time,
+            rounding: None,
+        })
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected {expected:?}, found {c:?}")),
+            None => Err(format!("expected {expected:?}, found end of line")),
+        }
+    }
+
+    /// Read an identifier up to one of the unescaped `stops`
+    fn read_until(&mut self, stops: &[char]) -> String {
+        let mut buf = String::new();
+
+        while let Some(c) = self.chars.next_if(|c| !stops.contains(c)) {
+            match c {
+                '\\' => match self.chars.next_if(|c| matches!(c, ',' | '=' | ' ' | '\\')) {
+                    Some(escaped) => buf.push(escaped),
+                    None => buf.push(c),
+                },
+                c => buf.push(c),
+            }
+        }
+
+        buf
+    }
+
+    fn read_value(&mut self) -> Result<Value, String> {
+        if self.chars.next_if_eq(&'"').is_some() {
+            let mut buf = String::new();
+
+            loop {
+                match self.chars.next() {
+                    Some('"') => return Ok(Value::String(buf)),
+                    Some('\\') => match self.chars.next_if(|c| matches!(c, '"' | '\\')) {
+                        Some(escaped) => buf.push(escaped),
+                        None => buf.push('\\'),
+                    },
+                    Some(c) => buf.push(c),
+                    None => return Err("unterminated string field".to_owned()),
+                }
+            }
+        }
+
+        let raw = self.read_until(&[',', ' ']);
+
+        if let Some(i) = raw.strip_suffix('i') {
+            i.parse().map(Value::Integer).map_err(|e| e.to_string())
+        } else if let Some(u) = raw.strip_suffix('u') {
+            u.parse().map(Value::UInteger).map_err(|e| e.to_string())
+        } else {
+            match raw.as_str() {
+                "t" | "T" | "true" | "True" | "TRUE" => Ok(Value::Boolean(true)),
+                "f" | "F" | "false" | "False" | "FALSE" => Ok(Value::Boolean(false)),
+                _ => raw
+                    .parse()
+                    .map(Value::Float)
+                    .map_err(|_| format!("invalid field value {raw}")),
+            }
+        }
+    }
+}
+
 pub struct DataPointBuilder<const HAS_FIELD: bool = false> {
     data_point: DataPoint,
 }
@@ -87,6 +377,7 @@ impl DataPointBuilder {
                 tags: Default::default(),
                 fields: Default::default(),
                 time: None,
+                rounding: None,
             },
         }
     }
@@ -105,6 +396,16 @@ impl<const HAS_FIELD: bool> DataPointBuilder<HAS_FIELD> {
         }
     }
 
+    /// Add the field if `value` is `Some`, this does not count as the point's required field
+    pub fn with_field_opt<K: Into<String>, V: Into<Value>>(self, key: K, value: Option<V>) -> Self {
+        match value {
+            Some(value) => DataPointBuilder {
+                data_point: self.with_field(key, value).data_point,
+            },
+            None => self,
+        }
+    }
+
     pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.data_point.tags.insert(key.into(), value.into());
         self
@@ -114,6 +415,12 @@ impl<const HAS_FIELD: bool> DataPointBuilder<HAS_FIELD> {
         self.data_point.time = Some(time.into());
         self
     }
+
+    /// Round the timestamp with `rounding` instead of the writer's rounding mode
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.data_point.rounding = Some(rounding);
+        self
+    }
 }
 
 /// Convert DataPointBuilder into the underlying DataPoint
@@ -135,7 +442,7 @@ impl From<DataPointBuilder<true>> for DataPoint {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum Value {
     Float(f64),
     Integer(i64),
@@ -192,46 +499,269 @@ impl From<bool> for Value {
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+macro_rules! impl_from_widening {
+    ($variant:ident($target:ty): $($source:ty),*) => {
+        $(
+            impl From<$source> for Value {
+                fn from(value: $source) -> Self {
+                    Self::$variant(<$target>::from(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_widening!(Integer(i64): i8, i16, i32);
+impl_from_widening!(UInteger(u64): u8, u16, u32);
+impl_from_widening!(Float(f64): f32);
+
+/// Only available where `isize` fits into an `i64` without loss
+#[cfg(any(
+    target_pointer_width = "16",
+    target_pointer_width = "32",
+    target_pointer_width = "64"
+))]
+impl From<isize> for Value {
+    fn from(value: isize) -> Self {
+        Self::Integer(value as i64)
+    }
+}
+
+/// Only available where `usize` fits into a `u64` without loss
+#[cfg(any(
+    target_pointer_width = "16",
+    target_pointer_width = "32",
+    target_pointer_width = "64"
+))]
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Self::UInteger(value as u64)
+    }
+}
+
+impl From<char> for Value {
+    fn from(value: char) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<Cow<'_, str>> for Value {
+    fn from(value: Cow<'_, str>) -> Self {
+        Self::String(value.into_owned())
+    }
+}
+
+impl From<Arc<str>> for Value {
+    fn from(value: Arc<str>) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+/// How a [Duration] is written as a field value
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
+pub enum DurationFormat {
+    /// Integer nanoseconds, durations beyond `i64::MAX` (about 292 years) can't be written
+    #[default]
+    Nanos,
+    /// Float seconds
+    Seconds,
+}
+
+impl Value {
+    /// Fails with [ConversionError::DurationOverflow] if the nanoseconds don't fit into an `i64`
+    pub fn from_duration(
+        duration: Duration,
+        format: DurationFormat,
+    ) -> Result<Self, ConversionError> {
+        match format {
+            DurationFormat::Nanos => match i64::try_from(duration.as_nanos()) {
+                Ok(nanos) => Ok(Self::Integer(nanos)),
+                Err(_) => Err(ConversionError::DurationOverflow(duration)),
+            },
+            DurationFormat::Seconds => Ok(Self::Float(duration.as_secs_f64())),
+        }
+    }
+}
+
+/// Integer nanoseconds, see [Value::from_duration] for other formats
+impl TryFrom<Duration> for Value {
+    type Error = ConversionError;
+
+    fn try_from(value: Duration) -> Result<Self, Self::Error> {
+        Self::from_duration(value, DurationFormat::default())
+    }
+}
+
+/// A point in time, stored in UTC
+///
+/// Converts from chrono's `DateTime` in any time zone and from [SystemTime]. Other types that
+/// implement `Into<DateTime<Utc>>` have to be converted to a `DateTime` first, there is no
+/// blanket conversion for them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Timestamp {
     inner: DateTime<Utc>,
 }
 
 impl Timestamp {
-    fn to_line_protocol(&self, precision: WritePrecision) -> Result<String, ConversionError> {
-        Ok(match precision {
-            WritePrecision::NS => self
-                .inner
-                .timestamp_nanos_opt()
-                .ok_or(ConversionError::TimeConversionError(
-                    "Can not convert ridiculously large date with nanosecond precision".to_owned(),
-                ))?
-                .to_string(),
-            WritePrecision::US => self.inner.timestamp_micros().to_string(),
-            WritePrecision::MS => self.inner.timestamp_millis().to_string(),
-            WritePrecision::S => self.inner.timestamp().to_string(),
-        })
+    fn to_line_protocol(
+        self,
+        precision: WritePrecision,
+        rounding: Rounding,
+    ) -> Result<String, ConversionError> {
+        let unit: i128 = match precision {
+            WritePrecision::NS => 1,
+            WritePrecision::US => 1_000,
+            WritePrecision::MS => 1_000_000,
+            WritePrecision::S => 1_000_000_000,
+            WritePrecision::Auto => return Err(ConversionError::UnresolvedPrecision),
+        };
+        let nanos = i128::from(self.inner.timestamp()) * 1_000_000_000
+            + i128::from(self.inner.timestamp_subsec_nanos());
+
+        let floor = nanos.div_euclid(unit);
+        let remainder = nanos.rem_euclid(unit);
+        let rounded = match rounding {
+            _ if remainder == 0 => floor,
+            Rounding::Floor => floor,
+            Rounding::Ceil => floor + 1,
+            Rounding::HalfEven => match (2 * remainder).cmp(&unit) {
+                Ordering::Less => floor,
+                Ordering::Greater => floor + 1,
+                Ordering::Equal => floor + floor.rem_euclid(2),
+            },
+            Rounding::Strict => return Err(ConversionError::PrecisionLoss(precision)),
+        };
+
+        i64::try_from(rounded)
+            .map(|timestamp| timestamp.to_string())
+            .map_err(|_| {
+                ConversionError::TimeConversionError(format!(
+                    "Can not convert ridiculously large date with {precision} precision"
+                ))
+            })
+    }
+
+    /// The coarsest precision representing this timestamp without loss
+    fn lossless_precision(self) -> WritePrecision {
+        match self.inner.timestamp_subsec_nanos() {
+            n if n % 1_000_000_000 == 0 => WritePrecision::S,
+            n if n % 1_000_000 == 0 => WritePrecision::MS,
+            n if n % 1_000 == 0 => WritePrecision::US,
+            _ => WritePrecision::NS,
+        }
+    }
+}
+
+impl Timestamp {
+    /// Format with as many fractional digits as needed, as expected by the management api
+    pub(crate) fn to_rfc3339(self) -> String {
+        self.inner.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    }
+
+    pub(crate) fn from_line_protocol(timestamp: i64, precision: WritePrecision) -> Option<Self> {
+        let inner = match precision {
+            WritePrecision::NS => DateTime::from_timestamp_nanos(timestamp),
+            WritePrecision::US => DateTime::from_timestamp_micros(timestamp)?,
+            WritePrecision::MS => DateTime::from_timestamp_millis(timestamp)?,
+            WritePrecision::S => DateTime::from_timestamp(timestamp, 0)?,
+            WritePrecision::Auto => return None,
+        };
+
+        Some(Self { inner })
+    }
+}
+
+impl Timestamp {
+    /// Nanoseconds since the unix epoch, the full range of line protocol timestamps
+    pub fn from_nanos(nanos: i64) -> Self {
+        Self {
+            inner: DateTime::from_timestamp_nanos(nanos),
+        }
+    }
+
+    /// Microseconds since the unix epoch, `None` if out of range
+    pub fn from_micros(micros: i64) -> Option<Self> {
+        Self::from_line_protocol(micros, WritePrecision::US)
+    }
+
+    /// Milliseconds since the unix epoch, `None` if out of range
+    pub fn from_millis(millis: i64) -> Option<Self> {
+        Self::from_line_protocol(millis, WritePrecision::MS)
+    }
+
+    /// Seconds since the unix epoch, `None` if out of range
+    pub fn from_secs(secs: i64) -> Option<Self> {
+        Self::from_line_protocol(secs, WritePrecision::S)
+    }
+
+    /// A wall clock time in `tz`, `None` if it does not exist or is ambiguous there, e.g. during
+    /// a daylight saving time change
+    pub fn from_local<Tz: TimeZone>(local: NaiveDateTime, tz: &Tz) -> Option<Self> {
+        Some(tz.from_local_datetime(&local).single()?.into())
+    }
+
+    /// Nanoseconds since the unix epoch, `None` if the timestamp is beyond what an `i64` holds
+    pub fn nanos(self) -> Option<i64> {
+        self.inner.timestamp_nanos_opt()
+    }
+
+    pub fn to_datetime(self) -> DateTime<Utc> {
+        self.inner
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for Timestamp {
+    fn from(value: DateTime<Tz>) -> Self {
+        Self {
+            inner: value.to_utc(),
+        }
     }
 }
 
-impl<T: Into<DateTime<Utc>>> From<T> for Timestamp {
-    fn from(value: T) -> Self {
+impl From<SystemTime> for Timestamp {
+    fn from(value: SystemTime) -> Self {
         Self {
             inner: value.into(),
         }
     }
 }
 
+/// Fails for dates beyond what chrono supports, which `time` allows with its `large-dates` feature
+#[cfg(feature = "time")]
+impl TryFrom<time::OffsetDateTime> for Timestamp {
+    type Error = ConversionError;
+
+    fn try_from(value: time::OffsetDateTime) -> Result<Self, Self::Error> {
+        match DateTime::from_timestamp(value.unix_timestamp(), value.nanosecond()) {
+            Some(inner) => Ok(Self { inner }),
+            None => Err(ConversionError::TimeConversionError(format!(
+                "Can not convert out of range date {value}"
+            ))),
+        }
+    }
+}
+
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(value: Timestamp) -> Self {
+        value.inner
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use std::borrow::Cow;
     use std::collections::HashMap;
-    use std::time::SystemTime;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
 
-    use chrono::DateTime;
+    use chrono::{DateTime, FixedOffset};
 
-    use crate::influx::Timestamp;
     use crate::influx::Value::{Boolean, Float, Integer, String, UInteger};
-    use crate::{DataPoint, DataPointBuilder};
+    use crate::influx::{
+        parse_line_protocol, resolve_precision, to_line_protocol_chunks, DurationFormat,
+        LineProtocol, Timestamp, Value,
+    };
+    use crate::{ConversionError, DataPoint, DataPointBuilder, Rounding, WritePrecision};
 
     #[test]
     fn datapoint_builder() {
@@ -267,4 +797,303 @@ mod test {
             point.time
         )
     }
+
+    #[test]
+    fn timestamp_conversions() {
+        let expected = Timestamp::from(DateTime::from_timestamp(1630424257, 123_456_789).unwrap());
+
+        assert_eq!(expected, Timestamp::from_nanos(1630424257123456789));
+        assert_eq!(Some(1630424257123456789), expected.nanos());
+        assert_eq!(
+            Timestamp::from(DateTime::from_timestamp(1630424257, 123_456_000).unwrap()),
+            Timestamp::from_micros(1630424257123456).unwrap()
+        );
+        assert_eq!(
+            Timestamp::from(DateTime::from_timestamp(1630424257, 123_000_000).unwrap()),
+            Timestamp::from_millis(1630424257123).unwrap()
+        );
+        assert_eq!(
+            Timestamp::from(DateTime::from_timestamp(1630424257, 0).unwrap()),
+            Timestamp::from_secs(1630424257).unwrap()
+        );
+        assert_eq!(None, Timestamp::from_secs(i64::MAX));
+
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(
+            expected,
+            Timestamp::from(expected.to_datetime().with_timezone(&offset))
+        );
+        assert_eq!(
+            Some(expected),
+            Timestamp::from_local(
+                expected.to_datetime().with_timezone(&offset).naive_local(),
+                &offset
+            )
+        );
+
+        #[cfg(feature = "time")]
+        assert_eq!(
+            expected,
+            Timestamp::try_from(
+                time::OffsetDateTime::from_unix_timestamp_nanos(1630424257123456789).unwrap()
+            )
+            .unwrap()
+        );
+        #[cfg(feature = "time")]
+        assert!(Timestamp::try_from(
+            time::Date::from_calendar_date(999_999, time::Month::January, 1)
+                .unwrap()
+                .midnight()
+                .assume_utc()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn timestamp_rounding() {
+        let round = |nanos, precision, rounding| {
+            Timestamp::from_nanos(nanos).to_line_protocol(precision, rounding)
+        };
+
+        assert_eq!(
+            "1",
+            round(1_500, WritePrecision::US, Rounding::Floor).unwrap()
+        );
+        assert_eq!(
+            "2",
+            round(1_500, WritePrecision::US, Rounding::Ceil).unwrap()
+        );
+        assert_eq!(
+            "2",
+            round(1_500, WritePrecision::US, Rounding::HalfEven).unwrap()
+        );
+        assert_eq!(
+            "2",
+            round(2_500, WritePrecision::US, Rounding::HalfEven).unwrap()
+        );
+        assert_eq!(
+            "3",
+            round(2_501, WritePrecision::US, Rounding::HalfEven).unwrap()
+        );
+        assert_eq!(
+            "-2",
+            round(-1_500, WritePrecision::US, Rounding::Floor).unwrap()
+        );
+        assert_eq!(
+            "-1",
+            round(-1_500, WritePrecision::US, Rounding::Ceil).unwrap()
+        );
+        assert_eq!(
+            "-2",
+            round(-1_500, WritePrecision::US, Rounding::HalfEven).unwrap()
+        );
+        assert_eq!(
+            "2",
+            round(2_000, WritePrecision::US, Rounding::Strict).unwrap()
+        );
+        assert!(matches!(
+            round(2_001, WritePrecision::US, Rounding::Strict),
+            Err(ConversionError::PrecisionLoss(WritePrecision::US))
+        ));
+        assert_eq!(
+            "1630424258",
+            round(
+                1_630_424_257_600_000_000,
+                WritePrecision::S,
+                Rounding::HalfEven
+            )
+            .unwrap()
+        );
+
+        let point: DataPoint = DataPointBuilder::new("m")
+            .with_field("f", 0i64)
+            .with_time(Timestamp::from_nanos(1_999_999))
+            .with_rounding(Rounding::Ceil)
+            .into();
+        assert_eq!(
+            "m f=0i 2",
+            [point].to_line_protocol(WritePrecision::MS).unwrap()
+        );
+    }
+
+    #[test]
+    fn auto_precision() {
+        let point = |secs, nanos| -> DataPoint {
+            DataPointBuilder::new("m")
+                .with_field("f", 0i64)
+                .with_time(DateTime::from_timestamp(secs, nanos).unwrap())
+                .into()
+        };
+        let untimed: DataPoint = DataPointBuilder::new("m").with_field("f", 0i64).into();
+        let resolve = |points: &[DataPoint]| resolve_precision(points, WritePrecision::Auto);
+
+        assert_eq!(WritePrecision::S, resolve(&[untimed]));
+        assert_eq!(WritePrecision::S, resolve(&[point(1, 0), point(2, 0)]));
+        assert_eq!(
+            WritePrecision::MS,
+            resolve(&[point(1, 0), point(1, 5_000_000)])
+        );
+        assert_eq!(
+            WritePrecision::US,
+            resolve(&[point(1, 5_000), point(1, 5_000_000)])
+        );
+        assert_eq!(WritePrecision::NS, resolve(&[point(1, 5), point(1, 0)]));
+        // nanoseconds overflow after 2262
+        assert_eq!(
+            WritePrecision::US,
+            resolve(&[point(1, 5), point(10_000_000_000, 0)])
+        );
+        assert_eq!(
+            WritePrecision::MS,
+            resolve_precision(&[point(1, 5)], WritePrecision::MS)
+        );
+
+        assert!(matches!(
+            [point(1, 0)].to_line_protocol(WritePrecision::Auto),
+            Err(ConversionError::UnresolvedPrecision)
+        ));
+    }
+
+    #[test]
+    fn datapoint_accessors() {
+        let mut point: DataPoint = DataPointBuilder::new("measurement")
+            .with_tag("tag", "value")
+            .with_field("field", 1i64)
+            .into();
+        let original = point.clone();
+
+        assert_eq!("measurement", point.measurement());
+        assert_eq!(Some("value"), point.tag("tag"));
+        assert_eq!(Some(&Integer(1)), point.field("field"));
+        assert_eq!(None, point.time());
+
+        point.set_measurement("other");
+        assert_eq!(Some("value".to_owned()), point.set_tag("tag", "changed"));
+        point.set_tag("added", "tag");
+        assert_eq!(Some("tag".to_owned()), point.remove_tag("added"));
+        assert_eq!(vec![("tag", "changed")], point.tags().collect::<Vec<_>>());
+
+        assert!(matches!(
+            point.remove_field("field"),
+            Err(ConversionError::MissingField)
+        ));
+        point.set_field("float", 0.5);
+        assert_eq!(Some(Integer(1)), point.remove_field("field").unwrap());
+        assert_eq!(None, point.remove_field("missing").unwrap());
+        assert_eq!(
+            vec![("float", &Float(0.5))],
+            point.fields().collect::<Vec<_>>()
+        );
+
+        point.set_time(SystemTime::UNIX_EPOCH);
+        assert_eq!(Some(Timestamp::from_nanos(0)), point.clear_time());
+
+        assert_ne!(original, point);
+        assert_eq!(
+            "other,tag=changed float=0.5",
+            [point].to_line_protocol(WritePrecision::NS).unwrap()
+        );
+    }
+
+    #[test]
+    fn value_conversions() {
+        assert_eq!(Integer(-3), Value::from(-3i8));
+        assert_eq!(Integer(-3), Value::from(-3i16));
+        assert_eq!(Integer(-3), Value::from(-3i32));
+        assert_eq!(Integer(-3), Value::from(-3isize));
+        assert_eq!(UInteger(3), Value::from(3u8));
+        assert_eq!(UInteger(3), Value::from(3u16));
+        assert_eq!(UInteger(3), Value::from(3u32));
+        assert_eq!(UInteger(3), Value::from(3usize));
+        assert_eq!(Float(21.5), Value::from(21.5f32));
+        assert_eq!(String("c".to_owned()), Value::from('c'));
+        assert_eq!(String("cow".to_owned()), Value::from(Cow::Borrowed("cow")));
+        assert_eq!(
+            String("arc".to_owned()),
+            Value::from(Arc::<str>::from("arc"))
+        );
+        assert_eq!(
+            Integer(1_500_000_000),
+            Value::try_from(Duration::from_millis(1500)).unwrap()
+        );
+        assert_eq!(
+            Float(1.5),
+            Value::from_duration(Duration::from_millis(1500), DurationFormat::Seconds).unwrap()
+        );
+        assert_eq!(
+            Integer(i64::MAX),
+            Value::try_from(Duration::from_nanos(i64::MAX as u64)).unwrap()
+        );
+        assert!(matches!(
+            Value::try_from(Duration::from_nanos(i64::MAX as u64 + 1)),
+            Err(ConversionError::DurationOverflow(_))
+        ));
+        assert!(Value::from_duration(Duration::MAX, DurationFormat::Seconds).is_ok());
+
+        let point: DataPoint = DataPointBuilder::new("m")
+            .with_field_opt("missing", None::<f64>)
+            .with_field_opt("present", Some(1u32))
+            .with_field("count", 3u32)
+            .into();
+        assert_eq!(None, point.field("missing"));
+        assert_eq!(Some(&UInteger(1)), point.field("present"));
+    }
+
+    #[test]
+    fn line_protocol_chunks() {
+        let points = (0..5).map(|i| DataPointBuilder::new("m").with_field("f", i as i64).into());
+
+        // every line is 6 bytes long, so two lines plus separator fit into 13 bytes
+        assert_eq!(
+            vec!["m f=0i\nm f=1i", "m f=2i\nm f=3i", "m f=4i"],
+            to_line_protocol_chunks(points, WritePrecision::NS, 13).unwrap()
+        );
+
+        let point: DataPoint = DataPointBuilder::new("m").with_field("f", 0i64).into();
+        assert!(matches!(
+            to_line_protocol_chunks([point], WritePrecision::NS, 5),
+            Err(ConversionError::LineTooLong(6, 5))
+        ));
+    }
+
+    #[test]
+    fn parse_line_protocol_escapes() {
+        let points = parse_line_protocol(
+            "my\\ measurement,tag\\=key=tag\\,value i=-1i,u=1u,f=1.5,b=T,s=\"say \\\"hi\\\"\" 1000\n\
+             # comment\n\
+             other s=\"a b,c\"",
+            WritePrecision::MS,
+        )
+        .unwrap();
+
+        assert_eq!(2, points.len());
+        assert_eq!("my measurement", points[0].measurement);
+        assert_eq!(
+            HashMap::from([("tag=key".to_owned(), "tag,value".to_owned())]),
+            points[0].tags
+        );
+        assert_eq!(
+            HashMap::from([
+                ("i".to_owned(), Integer(-1)),
+                ("u".to_owned(), UInteger(1)),
+                ("f".to_owned(), Float(1.5)),
+                ("b".to_owned(), Boolean(true)),
+                ("s".to_owned(), String("say \"hi\"".to_owned()))
+            ]),
+            points[0].fields
+        );
+        assert_eq!(
+            Some(Timestamp {
+                inner: DateTime::from_timestamp(1, 0).unwrap()
+            }),
+            points[0].time
+        );
+        assert_eq!(
+            HashMap::from([("s".to_owned(), String("a b,c".to_owned()))]),
+            points[1].fields
+        );
+
+        assert!(parse_line_protocol("measurement", WritePrecision::NS).is_err());
+        assert!(parse_line_protocol("measurement f=x", WritePrecision::NS).is_err());
+    }
 }
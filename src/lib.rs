@@ -1,4 +1,8 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub use http;
 use http::header::InvalidHeaderValue;
@@ -6,30 +10,207 @@ use http::{header, HeaderName, HeaderValue, Method, Request, Uri};
 use thiserror::Error;
 use url::Url;
 
+/// Derive `From<T> for DataPoint`
+///
+/// ```
+/// # use influx_write::{DataPoint, IntoDataPoint, Timestamp};
+/// #[derive(IntoDataPoint)]
+/// #[influx(measurement = "cpu")]
+/// struct Cpu {
+///     #[influx(tag)]
+///     host: String,
+///     #[influx(field, rename = "usage_user")]
+///     user: f64,
+///     #[influx(field)]
+///     temperature: Option<f64>,
+///     #[influx(timestamp)]
+///     time: Timestamp,
+///     #[influx(skip)]
+///     _sampled_by: &'static str,
+/// }
+///
+/// let point: DataPoint = Cpu {
+///     host: "a".to_owned(),
+///     user: 0.5,
+///     temperature: None,
+///     time: Timestamp::from_nanos(0),
+///     _sampled_by: "agent",
+/// }
+/// .into();
+/// assert_eq!(Some("a"), point.tag("host"));
+/// ```
+///
+/// Like [DataPointBuilder], a struct without a guaranteed field does not compile:
+/// ```compile_fail
+/// # use influx_write::IntoDataPoint;
+/// #[derive(IntoDataPoint)]
+/// struct Cpu {
+///     #[influx(tag)]
+///     host: String,
+///     #[influx(field)]
+///     user: Option<f64>,
+/// }
+/// ```
+#[cfg(feature = "derive")]
+pub use influx_write_derive::IntoDataPoint;
 pub use r#async::*;
 
+use crate::cardinality::CardinalityGuard;
+use crate::clock::Clock;
+pub use crate::health::{Health, HealthStatus, Ping};
 pub use crate::influx::DataPoint;
 pub use crate::influx::DataPointBuilder;
-use crate::influx::LineProtocol;
+use crate::influx::{resolve_precision, LineProtocol};
+pub use crate::influx::{DurationFormat, Timestamp, Value};
+use crate::schema::SchemaRegistry;
 
 mod r#async;
 pub mod blocking;
+pub mod buckets;
+pub mod cardinality;
+pub mod clock;
+pub mod delete;
+mod health;
 mod influx;
+pub mod query;
+pub mod recording;
+pub mod schema;
+pub mod serialize;
+pub mod sql;
+#[cfg(feature = "test-server")]
+pub mod test_server;
 
 pub const API_ENDPOINT_V2: &str = "/api/v2/write";
+pub const API_ENDPOINT_PING: &str = "/ping";
+pub const API_ENDPOINT_HEALTH: &str = "/health";
 
+#[derive(Clone)]
 pub struct InfluxWriter<W> {
     client: W,
     url: Url,
     authorization: Authorization,
     org: String,
     bucket: String,
+    default_tags: HashMap<String, String>,
+    clock: Option<Arc<dyn Clock>>,
+    rounding: Rounding,
+    schema: Option<SchemaRegistry>,
+    cardinality_guard: Option<CardinalityGuard>,
 }
 
 impl<W> InfluxWriter<W> {
+    /// Add a tag to every point written, tags set on the point itself take precedence
+    pub fn with_default_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set_default_tag(key, value);
+        self
+    }
+
+    /// Add several tags to every point written, see [InfluxWriter::with_default_tag]
+    pub fn with_default_tags<K: Into<String>, V: Into<String>>(
+        mut self,
+        tags: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.default_tags
+            .extend(tags.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Add or replace a default tag, applies to all following writes
+    pub fn set_default_tag(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.default_tags.insert(key.into(), value.into());
+    }
+
+    pub fn remove_default_tag(&mut self, key: &str) -> Option<String> {
+        self.default_tags.remove(key)
+    }
+
+    pub fn default_tags(&self) -> &HashMap<String, String> {
+        &self.default_tags
+    }
+
+    /// Stamp points without a timestamp with the time `clock` reports when they are written
+    ///
+    /// Otherwise the server assigns the time the request arrives. All untimed points of one write
+    /// get the same timestamp. When the writer is used as a tower service behind a retry layer,
+    /// stamp the points in front of the retry layer with `tower::ClientTimestampLayer`, otherwise
+    /// every attempt stamps them anew.
+    pub fn with_client_timestamps(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Rounding of points that do not set their own, see [DataPointBuilder::with_rounding]
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Check every batch against `schema` before it is sent, a conflicting batch is not written
+    ///
+    /// A learning schema only learns from batches that could be serialized.
+    pub fn with_schema(mut self, schema: SchemaRegistry) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Limit the series written per measurement, see [CardinalityGuard]
+    ///
+    /// Points are checked after default tags were added, their series are only tracked once the
+    /// batch also passed the schema. If every point of a batch is dropped, nothing is sent.
+    pub fn with_cardinality_guard(mut self, guard: CardinalityGuard) -> Self {
+        self.cardinality_guard = Some(guard);
+        self
+    }
+
+    /// Build the write request for `points`, `None` if no point is left to write
     pub(crate) fn build_request(
         &self,
-        point: impl IntoIterator<Item = DataPoint>,
+        points: impl IntoIterator<Item = DataPoint>,
+        precision: WritePrecision,
+    ) -> anyhow::Result<Option<Request<String>>> {
+        let mut points: Vec<DataPoint> = points
+            .into_iter()
+            .map(|mut point| {
+                point.apply_default_tags(&self.default_tags);
+                point.rounding_or_insert(self.rounding);
+                point
+            })
+            .collect();
+        if let Some(clock) = &self.clock {
+            stamp_untimed(&mut points, clock.as_ref());
+        }
+        // before the guard drops points, so schema errors refer to the batch as it was written
+        let mut learned = None;
+        if let Some(schema) = &self.schema {
+            learned = Some((schema, schema.stage(&mut points)?));
+        }
+        let mut staged = None;
+        if let Some(guard) = &self.cardinality_guard {
+            let (checked, batch) = guard.stage(points)?;
+            points = checked;
+            staged = Some((guard, batch));
+        }
+        let precision = resolve_precision(&points, precision);
+        let body = points.to_line_protocol(precision)?;
+
+        // only a batch that can be sent defines schemas and takes up series
+        if let Some((schema, learned)) = learned {
+            schema.commit(learned);
+        }
+        if let Some((guard, batch)) = staged {
+            guard.commit(batch);
+        }
+        if body.is_empty() {
+            // InfluxDB 2 rejects writes without points
+            return Ok(None);
+        }
+
+        self.build_write_request(body, precision).map(Some)
+    }
+
+    pub(crate) fn build_write_request(
+        &self,
+        body: String,
         precision: WritePrecision,
     ) -> anyhow::Result<Request<String>> {
         let mut url = self.url.clone();
@@ -46,10 +227,91 @@ impl<W> InfluxWriter<W> {
             .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
             .header(header::ACCEPT, "application/json")
             .method(Method::POST)
-            .body(point.to_line_protocol(precision)?)?)
+            .body(body)?)
+    }
+
+    /// Build a request for another api `endpoint` on the same server the writer writes to
+    pub(crate) fn build_api_request(
+        &self,
+        method: Method,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        body: String,
+    ) -> anyhow::Result<Request<String>> {
+        api_request(
+            &self.url,
+            &self.authorization,
+            method,
+            endpoint,
+            query,
+            body,
+        )
+    }
+}
+
+/// Give all points without a timestamp the same current time of `clock`
+pub(crate) fn stamp_untimed(points: &mut [DataPoint], clock: &dyn Clock) {
+    let now = clock.now();
+    for point in points {
+        point.time_or_insert(now);
+    }
+}
+
+/// Build a request for `endpoint` relative to `url`, a non-empty `body` is sent as json
+pub(crate) fn api_request(
+    url: &Url,
+    authorization: &Authorization,
+    method: Method,
+    endpoint: &str,
+    query: &[(&str, &str)],
+    body: String,
+) -> anyhow::Result<Request<String>> {
+    url_request(url.join(endpoint)?, authorization, method, query, body)
+}
+
+/// Build a request for `url`, a non-empty `body` is sent as json
+pub(crate) fn url_request(
+    mut url: Url,
+    authorization: &Authorization,
+    method: Method,
+    query: &[(&str, &str)],
+    body: String,
+) -> anyhow::Result<Request<String>> {
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+
+    let mut request = http::request::Builder::new()
+        .uri(Uri::try_from(url.as_str())?)
+        .header(header::USER_AGENT, "influx-write/0.0.0")
+        .header(header::AUTHORIZATION, authorization.header_value())
+        .header(header::ACCEPT, "application/json")
+        .method(method);
+    if !body.is_empty() {
+        request = request.header(header::CONTENT_TYPE, "application/json");
+    }
+
+    Ok(request.body(body)?)
+}
+
+/// Message of the `400 Bad Request` InfluxDB 2 answers a write without points with
+pub(crate) const EMPTY_WRITE_MESSAGE: &str = "writing requires points";
+
+/// Turn unsuccessful responses into errors carrying the response body
+pub(crate) fn error_for_status(
+    response: http::Response<Vec<u8>>,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(status_error(response.body()))
     }
 }
 
+pub(crate) fn status_error(body: &[u8]) -> anyhow::Error {
+    anyhow::anyhow!("Got response: {:?}", String::from_utf8(body.to_vec()))
+}
+
 // curl --request POST \
 // "http://localhost:8086/api/v2/write?org=YOUR_ORG&bucket=YOUR_BUCKET&precision=ns" \
 // --header "Authorization: Token YOUR_API_TOKEN" \
@@ -60,18 +322,17 @@ impl<W> InfluxWriter<W> {
 // airSensors,sensor_id=TLM0202 temperature=75.30007505999716,humidity=35.651929918691714,co=0.5141876544505826 1630424257000000000
 // '
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
 pub enum WritePrecision {
+    #[default]
     NS,
     US,
     MS,
     S,
-}
-
-impl Default for WritePrecision {
-    fn default() -> Self {
-        Self::NS
-    }
+    /// Pick the coarsest precision that loses no information, per batch
+    ///
+    /// Only supported by [InfluxWriter], which sends the chosen precision along with the points.
+    Auto,
 }
 
 impl Display for WritePrecision {
@@ -81,18 +342,64 @@ impl Display for WritePrecision {
             WritePrecision::US => write!(f, "us"),
             WritePrecision::MS => write!(f, "ms"),
             WritePrecision::S => write!(f, "s"),
+            WritePrecision::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for WritePrecision {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ns" => Ok(WritePrecision::NS),
+            "us" => Ok(WritePrecision::US),
+            "ms" => Ok(WritePrecision::MS),
+            "s" => Ok(WritePrecision::S),
+            _ => Err(ParseError::UnknownPrecision(s.to_owned())),
         }
     }
 }
 
+/// How timestamps are rounded when written with a precision coarser than nanoseconds
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// Round toward negative infinity, dropping the sub-unit digits of positive timestamps
+    #[default]
+    Floor,
+    /// Round toward positive infinity
+    Ceil,
+    /// Round to the nearest unit, ties to the even one
+    HalfEven,
+    /// Fail with [ConversionError::PrecisionLoss] instead of dropping non-zero sub-unit digits
+    Strict,
+}
+
 #[derive(Error, Debug)]
 pub enum ConversionError {
     #[error("")]
     TimeConversionError(String),
     #[error("Datapoints must have at least one field")]
     MissingField,
+    #[error("Line of {0} bytes exceeds the maximum size of {1} bytes")]
+    LineTooLong(usize, usize),
+    #[error("Automatic precision requires a writer that sends the precision with the points")]
+    UnresolvedPrecision,
+    #[error("Timestamp can not be written with precision {0} without losing digits")]
+    PrecisionLoss(WritePrecision),
+    #[error("Duration of {0:?} does not fit into an integer field as nanoseconds")]
+    DurationOverflow(Duration),
+}
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("Invalid line protocol in line {0}: {1}")]
+    InvalidLine(usize, String),
+    #[error("Unknown write precision {0}")]
+    UnknownPrecision(String),
 }
 
+#[derive(Clone)]
 pub enum Authorization {
     Token(HeaderValue),
 }
@@ -121,6 +428,7 @@ impl From<Authorization> for (HeaderName, HeaderValue) {
     }
 }
 
+#[cfg(any(feature = "reqwest", feature = "reqwest-blocking"))]
 #[derive(Error, Debug)]
 enum HttpClientError<E> {
     #[error(transparent)]
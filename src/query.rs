@@ -0,0 +1,569 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::Arc;
+
+use chrono::DateTime;
+use http::header::ACCEPT;
+use http::{HeaderValue, Method, Request};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{AsyncBody, InfluxWriter, Timestamp, Value};
+
+pub const API_ENDPOINT_QUERY: &str = "/api/v2/query";
+
+#[derive(Error, Debug)]
+pub enum QueryError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("Flux error: {message} (reference {reference})")]
+    Flux { message: String, reference: String },
+    #[error("Invalid {data_type} value {value:?} in column {column}")]
+    InvalidValue {
+        column: String,
+        data_type: String,
+        value: String,
+    },
+    #[error("Row before the #datatype annotation of its table")]
+    MissingHeader,
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("InfluxQL error: {0}")]
+    InfluxQl(String),
+}
+
+/// A value of a Flux result, time columns are kept apart from field values
+#[derive(Clone, Debug, PartialEq)]
+pub enum FluxValue {
+    Null,
+    Value(Value),
+    Time(Timestamp),
+}
+
+/// Column description taken from the `#datatype`, `#group` and `#default` annotations
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxColumn {
+    pub name: String,
+    pub data_type: String,
+    pub group: bool,
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxRecord {
+    columns: Arc<Vec<FluxColumn>>,
+    values: Vec<FluxValue>,
+}
+
+impl FluxRecord {
+    pub fn columns(&self) -> &[FluxColumn] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[FluxValue] {
+        &self.values
+    }
+
+    pub fn get(&self, column: &str) -> Option<&FluxValue> {
+        let index = self.columns.iter().position(|c| c.name == column)?;
+        self.values.get(index)
+    }
+
+    pub fn result(&self) -> Option<&str> {
+        match self.get("result")? {
+            FluxValue::Value(Value::String(result)) => Some(result),
+            _ => None,
+        }
+    }
+
+    pub fn table(&self) -> Option<i64> {
+        match self.get("table")? {
+            FluxValue::Value(Value::Integer(table)) => Some(*table),
+            _ => None,
+        }
+    }
+
+    pub fn time(&self) -> Option<Timestamp> {
+        match self.get("_time")? {
+            FluxValue::Time(time) => Some(*time),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> Option<&Value> {
+        match self.get("_value")? {
+            FluxValue::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn measurement(&self) -> Option<&str> {
+        self.string("_measurement")
+    }
+
+    pub fn field(&self) -> Option<&str> {
+        self.string("_field")
+    }
+
+    /// Value of a string column, e.g. a tag
+    pub fn string(&self, column: &str) -> Option<&str> {
+        match self.get(column)? {
+            FluxValue::Value(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxTable {
+    pub columns: Vec<FluxColumn>,
+    pub records: Vec<FluxRecord>,
+}
+
+/// Lazily decodes Flux annotated CSV into records
+///
+/// Works on any reader, so large results can be processed without collecting all records.
+pub struct FluxCsvReader<R: Read> {
+    csv: csv::Reader<R>,
+    decoder: FluxDecoder,
+}
+
+impl<R: Read> FluxCsvReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            csv: csv_reader(reader),
+            decoder: FluxDecoder::default(),
+        }
+    }
+
+    /// Read all records and group them into tables
+    pub fn into_tables(self) -> Result<Vec<FluxTable>, QueryError> {
+        let mut tables = Vec::new();
+
+        for record in self {
+            push_record(&mut tables, record?);
+        }
+
+        Ok(tables)
+    }
+
+    fn next_record(&mut self) -> Result<Option<FluxRecord>, QueryError> {
+        let mut row = csv::StringRecord::new();
+
+        while self.csv.read_record(&mut row)? {
+            if let Some(record) = self.decoder.decode(&row)? {
+                return Ok(Some(record));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<R: Read> Iterator for FluxCsvReader<R> {
+    type Item = Result<FluxRecord, QueryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Decodes Flux annotated CSV into records while the response body is still being received
+///
+/// Only complete rows are decoded, the rest of a chunk is kept until the next one arrives.
+pub struct FluxRecordStream {
+    body: Box<dyn AsyncBody>,
+    buffer: Vec<u8>,
+    scanned: usize,
+    quoted: bool,
+    decoder: FluxDecoder,
+    records: VecDeque<Result<FluxRecord, QueryError>>,
+    finished: bool,
+}
+
+impl FluxRecordStream {
+    pub fn new(body: Box<dyn AsyncBody>) -> Self {
+        Self {
+            body,
+            buffer: Vec::new(),
+            scanned: 0,
+            quoted: false,
+            decoder: FluxDecoder::default(),
+            records: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// The next record, `None` once the body is complete
+    pub async fn next_record(&mut self) -> anyhow::Result<Option<FluxRecord>> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Ok(Some(record?));
+            }
+            if self.finished {
+                return Ok(None);
+            }
+
+            match self.body.next_chunk().await? {
+                Some(chunk) => {
+                    self.buffer.extend_from_slice(&chunk);
+                    let end = self.complete_rows();
+                    self.decode(end);
+                }
+                None => {
+                    self.finished = true;
+                    self.decode(self.buffer.len());
+                }
+            }
+        }
+    }
+
+    /// Read all records and group them into tables
+    pub async fn into_tables(mut self) -> anyhow::Result<Vec<FluxTable>> {
+        let mut tables = Vec::new();
+
+        while let Some(record) = self.next_record().await? {
+            push_record(&mut tables, record);
+        }
+
+        Ok(tables)
+    }
+
+    /// Length of the buffered rows that are complete, quoted values may contain line breaks
+    fn complete_rows(&mut self) -> usize {
+        let mut end = 0;
+
+        for (i, byte) in self.buffer.iter().enumerate().skip(self.scanned) {
+            match byte {
+                b'"' => self.quoted = !self.quoted,
+                b'\n' if !self.quoted => end = i + 1,
+                _ => {}
+            }
+        }
+        self.scanned = self.buffer.len() - end;
+
+        end
+    }
+
+    fn decode(&mut self, end: usize) {
+        let mut csv = csv_reader(&self.buffer[..end]);
+        let mut row = csv::StringRecord::new();
+
+        loop {
+            match csv.read_record(&mut row) {
+                Ok(true) => {
+                    if let Some(record) = self.decoder.decode(&row).transpose() {
+                        self.records.push_back(record);
+                    }
+                }
+                Ok(false) => break,
+                Err(e) => {
+                    self.records.push_back(Err(e.into()));
+                    break;
+                }
+            }
+        }
+
+        self.buffer.drain(..end);
+    }
+}
+
+/// Annotations and header of the table that is currently decoded
+#[derive(Default)]
+struct FluxDecoder {
+    data_types: Vec<String>,
+    groups: Vec<bool>,
+    defaults: Vec<Option<String>>,
+    columns: Option<Arc<Vec<FluxColumn>>>,
+    error_table: bool,
+}
+
+impl FluxDecoder {
+    /// Decode one row, annotation and header rows only update the decoder
+    fn decode(&mut self, row: &csv::StringRecord) -> Result<Option<FluxRecord>, QueryError> {
+        let Some(first) = row.get(0) else {
+            return Ok(None);
+        };
+        let cells = || row.iter().skip(1).map(str::to_owned);
+
+        if first.starts_with('#') {
+            if self.columns.take().is_some() {
+                // annotations after data start a new block
+                self.data_types.clear();
+                self.groups.clear();
+                self.defaults.clear();
+            }
+
+            match first {
+                "#datatype" => self.data_types = cells().collect(),
+                "#group" => self.groups = cells().map(|g| g == "true").collect(),
+                "#default" => {
+                    self.defaults = cells().map(|d| (!d.is_empty()).then_some(d)).collect()
+                }
+                _ => {}
+            }
+            return Ok(None);
+        }
+
+        let Some(columns) = &self.columns else {
+            // queries request the annotations, without them the values can't be typed
+            if self.data_types.is_empty() {
+                return Err(QueryError::MissingHeader);
+            }
+
+            let names: Vec<String> = cells().collect();
+            self.error_table = names.first().is_some_and(|n| n == "error")
+                && names.iter().any(|n| n == "reference");
+            self.columns = Some(Arc::new(
+                names
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, name)| FluxColumn {
+                        name,
+                        data_type: self
+                            .data_types
+                            .get(i)
+                            .cloned()
+                            .unwrap_or_else(|| "string".to_owned()),
+                        group: self.groups.get(i).copied().unwrap_or_default(),
+                        default: self.defaults.get(i).cloned().flatten(),
+                    })
+                    .collect(),
+            ));
+            return Ok(None);
+        };
+
+        if self.error_table {
+            let mut cells = cells();
+            return Err(QueryError::Flux {
+                message: cells.next().unwrap_or_default(),
+                reference: cells.next().unwrap_or_default(),
+            });
+        }
+
+        let values = columns
+            .iter()
+            .zip(cells().chain(std::iter::repeat(String::new())))
+            .map(|(column, cell)| parse_value(column, cell))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(FluxRecord {
+            columns: columns.clone(),
+            values,
+        }))
+    }
+}
+
+fn csv_reader<R: Read>(reader: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader)
+}
+
+/// Append the record to the last table, or start a new one if it belongs to another table
+fn push_record(tables: &mut Vec<FluxTable>, record: FluxRecord) {
+    match tables.last_mut() {
+        Some(table)
+            if table.records[0].result() == record.result()
+                && table.records[0].table() == record.table()
+                && table.columns == *record.columns =>
+        {
+            table.records.push(record)
+        }
+        _ => tables.push(FluxTable {
+            columns: record.columns.to_vec(),
+            records: vec![record],
+        }),
+    }
+}
+
+fn parse_value(column: &FluxColumn, cell: String) -> Result<FluxValue, QueryError> {
+    let cell = match (cell.is_empty(), &column.default) {
+        (true, Some(default)) => default.clone(),
+        (true, None) => return Ok(FluxValue::Null),
+        (false, _) => cell,
+    };
+
+    let invalid = |cell: String| QueryError::InvalidValue {
+        column: column.name.clone(),
+        data_type: column.data_type.clone(),
+        value: cell,
+    };
+
+    Ok(match column.data_type.as_str() {
+        "long" => match cell.parse() {
+            Ok(i) => FluxValue::Value(Value::Integer(i)),
+            Err(_) => return Err(invalid(cell)),
+        },
+        "unsignedLong" => match cell.parse() {
+            Ok(u) => FluxValue::Value(Value::UInteger(u)),
+            Err(_) => return Err(invalid(cell)),
+        },
+        "double" => match cell.parse() {
+            Ok(f) => FluxValue::Value(Value::Float(f)),
+            Err(_) => return Err(invalid(cell)),
+        },
+        "boolean" => match cell.as_str() {
+            "true" => FluxValue::Value(Value::Boolean(true)),
+            "false" => FluxValue::Value(Value::Boolean(false)),
+            _ => return Err(invalid(cell)),
+        },
+        data_type if data_type.starts_with("dateTime") => {
+            match DateTime::parse_from_rfc3339(&cell) {
+                Ok(time) => FluxValue::Time(time.into()),
+                Err(_) => return Err(invalid(cell)),
+            }
+        }
+        _ => FluxValue::Value(Value::String(cell)),
+    })
+}
+
+#[derive(Serialize)]
+struct QueryRequest<'a> {
+    query: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    dialect: Dialect,
+}
+
+#[derive(Serialize)]
+struct Dialect {
+    annotations: [&'static str; 3],
+    header: bool,
+    delimiter: &'static str,
+}
+
+impl<W> InfluxWriter<W> {
+    pub(crate) fn build_query_request(&self, flux: &str) -> anyhow::Result<Request<String>> {
+        let body = serde_json::to_string(&QueryRequest {
+            query: flux,
+            kind: "flux",
+            dialect: Dialect {
+                annotations: ["datatype", "group", "default"],
+                header: true,
+                delimiter: ",",
+            },
+        })?;
+
+        let mut req = self.build_api_request(
+            Method::POST,
+            API_ENDPOINT_QUERY,
+            &[("org", &self.org)],
+            body,
+        )?;
+        req.headers_mut()
+            .insert(ACCEPT, HeaderValue::from_static("application/csv"));
+
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::DateTime;
+
+    use crate::query::{FluxCsvReader, FluxRecordStream, FluxValue, QueryError};
+    use crate::{AsyncBody, ChunkFuture, Value};
+
+    const RESULT: &str = "\
+#datatype,string,long,dateTime:RFC3339,double,string,string
+#group,false,false,false,false,true,true
+#default,_result,,,,,
+,result,table,_time,_value,_field,sensor_id
+,,0,2021-08-31T15:37:37Z,73.5,temperature,\"TLM,0201\"
+,,0,2021-08-31T15:37:47Z,,temperature,\"TLM,0201\"
+,,1,2021-08-31T15:37:37Z,35.2,humidity,TLM0201
+
+#datatype,string,long,string
+#group,false,false,true
+#default,other,,
+,result,table,_field
+,,0,co
+";
+
+    #[test]
+    fn parse_annotated_csv() {
+        let tables = FluxCsvReader::new(RESULT.as_bytes()).into_tables().unwrap();
+
+        assert_eq!(3, tables.len());
+        assert_eq!(2, tables[0].records.len());
+
+        let record = &tables[0].records[0];
+        assert_eq!(Some("_result"), record.result());
+        assert_eq!(Some(0), record.table());
+        assert_eq!(Some(&Value::Float(73.5)), record.value());
+        assert_eq!(Some("temperature"), record.field());
+        assert_eq!(Some("TLM,0201"), record.string("sensor_id"));
+        assert_eq!(
+            Some(DateTime::from_timestamp(1630424257, 0).unwrap().into()),
+            record.time()
+        );
+        assert!(tables[0].columns[4].group);
+
+        assert_eq!(Some(&FluxValue::Null), tables[0].records[1].get("_value"));
+        assert_eq!(Some("other"), tables[2].records[0].result());
+        assert_eq!(Some("co"), tables[2].records[0].field());
+    }
+
+    #[test]
+    fn parse_error_table() {
+        let error = FluxCsvReader::new(
+            "#datatype,string,string\n#group,true,true\n#default,,\n,error,reference\n,type error,897\n"
+                .as_bytes(),
+        )
+        .into_tables()
+        .unwrap_err();
+
+        assert!(matches!(error, QueryError::Flux { reference, .. } if reference == "897"));
+    }
+
+    #[test]
+    fn missing_datatype() {
+        let error = FluxCsvReader::new(",result,table\n,,0\n".as_bytes())
+            .into_tables()
+            .unwrap_err();
+
+        assert!(matches!(error, QueryError::MissingHeader));
+    }
+
+    /// Hands out the body a few bytes at a time, splitting rows and quoted values
+    struct Chunks(Vec<u8>);
+
+    impl AsyncBody for Chunks {
+        fn next_chunk(&mut self) -> ChunkFuture<'_> {
+            let chunk: Vec<u8> = self.0.drain(..self.0.len().min(5)).collect();
+
+            Box::pin(async move { Ok((!chunk.is_empty()).then_some(chunk)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_chunks() {
+        let body = RESULT
+            .replace("TLM,0201", "TLM,\n0201")
+            .replace('\n', "\r\n");
+        let tables = FluxRecordStream::new(Box::new(Chunks(body.into_bytes())))
+            .into_tables()
+            .await
+            .unwrap();
+
+        assert_eq!(3, tables.len());
+        assert_eq!(2, tables[0].records.len());
+        assert_eq!(
+            Some("TLM,\r\n0201"),
+            tables[0].records[0].string("sensor_id")
+        );
+        assert_eq!(Some("co"), tables[2].records[0].field());
+
+        // the last row has no line break and is only decoded once the body is complete
+        let mut records = FluxRecordStream::new(Box::new(Chunks(
+            b"#datatype,string,long\n,result,table\n,,0\n,,x".to_vec(),
+        )));
+        assert_eq!(
+            Some(0),
+            records.next_record().await.unwrap().unwrap().table()
+        );
+        assert!(records.next_record().await.is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use http::{HeaderMap, Method, StatusCode, Uri};
+
+use crate::blocking::BlockingClient;
+use crate::influx::parse_line_protocol;
+use crate::{AsyncClient, DataPoint, WritePrecision, API_ENDPOINT_V2, EMPTY_WRITE_MESSAGE};
+
+/// In-memory client for testing code that uses [crate::InfluxWriter]
+///
+/// All clones share the recorded requests and the response script, so a clone can be handed to
+/// the writer while the test keeps another one for assertions. Without scripted responses every
+/// request is answered with `204 No Content`, except writes without points, which InfluxDB rejects
+/// with `400 Bad Request`.
+///
+/// ```
+/// # use influx_write::{Authorization, DataPointBuilder, InfluxWriter};
+/// # use influx_write::recording::RecordingClient;
+/// # fn main() -> anyhow::Result<()> {
+/// let client = RecordingClient::new();
+/// let mut writer = InfluxWriter::new_with_blocking_client(
+///     client.clone(),
+///     "http://localhost:8086".parse()?,
+///     Authorization::token("token")?,
+///     "org",
+///     "bucket",
+/// )?;
+///
+/// writer.write_single_blocking(
+///     DataPointBuilder::new("cpu")
+///         .with_tag("host", "a")
+///         .with_field("load", 0.5)
+///         .into(),
+/// )?;
+///
+/// client.assert_point("bucket", "cpu", &[("host", "a")]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct RecordingClient {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<RecordedRequest>,
+    responses: VecDeque<ScriptedResponse>,
+}
+
+/// A request captured by [RecordingClient]
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        url::form_urlencoded::parse(self.uri.query()?.as_bytes())
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    }
+
+    pub fn org(&self) -> Option<String> {
+        self.query_param("org")
+    }
+
+    pub fn bucket(&self) -> Option<String> {
+        self.query_param("bucket")
+    }
+
+    /// Whether this is a write of line protocol, as opposed to e.g. a query or delete
+    pub fn is_write(&self) -> bool {
+        self.method == Method::POST && self.uri.path().ends_with(API_ENDPOINT_V2)
+    }
+
+    pub fn precision(&self) -> anyhow::Result<WritePrecision> {
+        match self.query_param("precision") {
+            Some(precision) => Ok(precision.parse()?),
+            None => Ok(WritePrecision::default()),
+        }
+    }
+
+    /// Parse the line protocol body back into points
+    pub fn points(&self) -> anyhow::Result<Vec<DataPoint>> {
+        Ok(parse_line_protocol(&self.body, self.precision()?)?)
+    }
+}
+
+/// Response returned by [RecordingClient] for one request
+#[derive(Clone, Debug)]
+pub struct ScriptedResponse {
+    status: StatusCode,
+    body: String,
+    delay: Option<Duration>,
+    error: Option<String>,
+}
+
+impl ScriptedResponse {
+    pub fn status(status: StatusCode) -> Self {
+        Self {
+            status,
+            body: String::new(),
+            delay: None,
+            error: None,
+        }
+    }
+
+    /// Fail the request with a transport error instead of returning a response
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            error: Some(message.into()),
+            ..Self::status(StatusCode::NO_CONTENT)
+        }
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Wait before answering, in async code this does not block the executor
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn into_response(self) -> anyhow::Result<http::Response<Vec<u8>>> {
+        if let Some(error) = self.error {
+            return Err(anyhow!(error));
+        }
+
+        Ok(http::Response::builder()
+            .status(self.status)
+            .body(self.body.into_bytes())?)
+    }
+}
+
+impl RecordingClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a response for the next unanswered request
+    pub fn respond_with(&self, response: ScriptedResponse) -> &Self {
+        self.state().responses.push_back(response);
+        self
+    }
+
+    /// Queue one response per status code, in order
+    pub fn respond_with_statuses(&self, statuses: impl IntoIterator<Item = StatusCode>) -> &Self {
+        self.state()
+            .responses
+            .extend(statuses.into_iter().map(ScriptedResponse::status));
+        self
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.state().requests.len()
+    }
+
+    /// All points written to `bucket`, in order
+    pub fn points(&self, bucket: &str) -> anyhow::Result<Vec<DataPoint>> {
+        let mut points = Vec::new();
+
+        for request in self.requests() {
+            if request.is_write() && request.bucket().as_deref() == Some(bucket) {
+                points.extend(request.points()?);
+            }
+        }
+
+        Ok(points)
+    }
+
+    /// Whether `bucket` received a point of `measurement` carrying all of `tags`
+    ///
+    /// Fails if a write to `bucket` does not contain valid line protocol.
+    pub fn received_point(
+        &self,
+        bucket: &str,
+        measurement: &str,
+        tags: &[(&str, &str)],
+    ) -> anyhow::Result<bool> {
+        Ok(self.points(bucket)?.iter().any(|point| {
+            point.measurement() == measurement && tags.iter().all(|(k, v)| point.tag(k) == Some(*v))
+        }))
+    }
+
+    /// Panic unless `bucket` received a point of `measurement` carrying all of `tags`
+    #[track_caller]
+    pub fn assert_point(&self, bucket: &str, measurement: &str, tags: &[(&str, &str)]) {
+        let received = self
+            .received_point(bucket, measurement, tags)
+            .unwrap_or_else(|e| panic!("bucket {bucket:?} received invalid line protocol: {e}"));
+
+        if !received {
+            let received: Vec<String> = self
+                .requests()
+                .into_iter()
+                .filter(RecordedRequest::is_write)
+                .map(|request| format!("{:?}: {}", request.bucket(), request.body))
+                .collect();
+
+            panic!(
+                "bucket {bucket:?} received no point {measurement:?} with tags {tags:?}, got:\n{}",
+                received.join("\n")
+            );
+        }
+    }
+
+    /// Forget all recorded requests and scripted responses
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.requests.clear();
+        state.responses.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, req: http::Request<String>) -> ScriptedResponse {
+        let (parts, body) = req.into_parts();
+        let request = RecordedRequest {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            body,
+        };
+        let empty_write = request.is_write() && request.body.trim().is_empty();
+
+        let mut state = self.state();
+        state.requests.push(request);
+
+        state.responses.pop_front().unwrap_or_else(|| {
+            if empty_write {
+                ScriptedResponse::status(StatusCode::BAD_REQUEST).with_body(
+                    serde_json::json!({"code": "invalid", "message": EMPTY_WRITE_MESSAGE})
+                        .to_string(),
+                )
+            } else {
+                ScriptedResponse::status(StatusCode::NO_CONTENT)
+            }
+        })
+    }
+}
+
+impl AsyncClient for RecordingClient {
+    async fn execute(
+        &mut self,
+        req: http::Request<String>,
+    ) -> anyhow::Result<http::Response<Vec<u8>>> {
+        let response = self.record(req);
+
+        if let Some(delay) = response.delay {
+            Delay::new(delay).await;
+        }
+
+        response.into_response()
+    }
+}
+
+impl BlockingClient for RecordingClient {
+    fn execute(&mut self, req: http::Request<String>) -> anyhow::Result<http::Response<Vec<u8>>> {
+        let response = self.record(req);
+
+        if let Some(delay) = response.delay {
+            thread::sleep(delay);
+        }
+
+        response.into_response()
+    }
+}
+
+/// Runtime independent timer, sleeps on a helper thread and wakes the task afterwards
+struct Delay {
+    state: Arc<Mutex<(bool, Option<Waker>)>>,
+}
+
+impl Delay {
+    fn new(duration: Duration) -> Self {
+        let state = Arc::new(Mutex::new((false, None::<Waker>)));
+
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            thread::sleep(duration);
+
+            let mut state = thread_state.lock().unwrap_or_else(|e| e.into_inner());
+            state.0 = true;
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        });
+
+        Self { state }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use thiserror::Error;
+
+use crate::{DataPoint, Value};
+
+/// Type of a field value as stored by InfluxDB
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FieldType {
+    Float,
+    Integer,
+    UInteger,
+    String,
+    Boolean,
+}
+
+impl FieldType {
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Float(_) => FieldType::Float,
+            Value::Integer(_) => FieldType::Integer,
+            Value::UInteger(_) => FieldType::UInteger,
+            Value::String(_) => FieldType::String,
+            Value::Boolean(_) => FieldType::Boolean,
+        }
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::Float => write!(f, "float"),
+            FieldType::Integer => write!(f, "integer"),
+            FieldType::UInteger => write!(f, "unsigned"),
+            FieldType::String => write!(f, "string"),
+            FieldType::Boolean => write!(f, "boolean"),
+        }
+    }
+}
+
+/// Tag keys and field types of one measurement
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MeasurementSchema {
+    tags: HashSet<String>,
+    fields: HashMap<String, FieldType>,
+}
+
+impl MeasurementSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tag(mut self, key: impl Into<String>) -> Self {
+        self.tags.insert(key.into());
+        self
+    }
+
+    pub fn with_field(mut self, key: impl Into<String>, field_type: FieldType) -> Self {
+        self.fields.insert(key.into(), field_type);
+        self
+    }
+
+    pub fn has_tag(&self, key: &str) -> bool {
+        self.tags.contains(key)
+    }
+
+    pub fn field_type(&self, key: &str) -> Option<FieldType> {
+        self.fields.get(key).copied()
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, FieldType)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), *v))
+    }
+}
+
+/// A point that does not match the schema, `point` is its index in the written batch
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    #[error("Point {point}: unknown measurement {measurement}")]
+    UnknownMeasurement { point: usize, measurement: String },
+    #[error("Point {point} ({measurement}): unknown tag {tag}")]
+    UnknownTag {
+        point: usize,
+        measurement: String,
+        tag: String,
+    },
+    #[error("Point {point} ({measurement}): unknown field {field}")]
+    UnknownField {
+        point: usize,
+        measurement: String,
+        field: String,
+    },
+    #[error("Point {point} ({measurement}): field {field} is {actual}, but the schema expects {expected}")]
+    FieldTypeConflict {
+        point: usize,
+        measurement: String,
+        field: String,
+        expected: FieldType,
+        actual: FieldType,
+    },
+}
+
+/// Checks points against per-measurement schemas before they are written
+///
+/// All clones share the schemas, so a clone can be handed to [crate::InfluxWriter::with_schema]
+/// while another one is used to declare or inspect measurements at runtime.
+///
+/// Without learning, every measurement, tag and field has to be declared. With learning, the
+/// first write of a measurement or field defines its schema. A batch is checked as a whole, if
+/// any point is rejected nothing is learned from the batch.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaRegistry {
+    state: Arc<Mutex<State>>,
+}
+
+/// Measurements, tags and fields a checked batch would add, see [SchemaRegistry::commit]
+#[derive(Debug, Default)]
+pub(crate) struct LearnedSchemas {
+    measurements: HashMap<String, MeasurementSchema>,
+}
+
+impl LearnedSchemas {
+    fn schema(&mut self, measurement: &str) -> &mut MeasurementSchema {
+        self.measurements.entry(measurement.to_owned()).or_default()
+    }
+
+    fn has_tag(&self, measurement: &str, tag: &str) -> bool {
+        self.measurements
+            .get(measurement)
+            .is_some_and(|schema| schema.has_tag(tag))
+    }
+
+    fn field_type(&self, measurement: &str, field: &str) -> Option<FieldType> {
+        self.measurements.get(measurement)?.field_type(field)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    measurements: HashMap<String, MeasurementSchema>,
+    learn: bool,
+    coerce: bool,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Learn unknown measurements, tags and fields from the points written
+    pub fn with_learning(self) -> Self {
+        self.state().learn = true;
+        self
+    }
+
+    /// Convert field values to the expected type where that is lossless, e.g. `1i` to `1.0`
+    pub fn with_coercion(self) -> Self {
+        self.state().coerce = true;
+        self
+    }
+
+    pub fn with_measurement(self, name: impl Into<String>, schema: MeasurementSchema) -> Self {
+        self.declare(name, schema);
+        self
+    }
+
+    /// Add or replace the schema of a measurement
+    pub fn declare(&self, name: impl Into<String>, schema: MeasurementSchema) {
+        self.state().measurements.insert(name.into(), schema);
+    }
+
+    pub fn measurement(&self, name: &str) -> Option<MeasurementSchema> {
+        self.state().measurements.get(name).cloned()
+    }
+
+    /// Check `points`, coercing field values if enabled
+    pub fn check(&self, points: &mut [DataPoint]) -> Result<(), SchemaError> {
+        let learned = self.stage(points)?;
+        self.commit(learned);
+        Ok(())
+    }
+
+    /// Like [SchemaRegistry::check], but nothing is learned before [SchemaRegistry::commit]
+    pub(crate) fn stage(&self, points: &mut [DataPoint]) -> Result<LearnedSchemas, SchemaError> {
+        let state = self.state();
+        let mut learned = LearnedSchemas::default();
+
+        for (index, point) in points.iter_mut().enumerate() {
+            let measurement = point.measurement().to_owned();
+            let schema = state.measurements.get(&measurement);
+            if schema.is_none() && !state.learn {
+                return Err(SchemaError::UnknownMeasurement {
+                    point: index,
+                    measurement,
+                });
+            }
+
+            for (tag, _) in point.tags() {
+                if schema.is_some_and(|s| s.has_tag(tag)) || learned.has_tag(&measurement, tag) {
+                    continue;
+                }
+                if !state.learn {
+                    return Err(SchemaError::UnknownTag {
+                        point: index,
+                        measurement,
+                        tag: tag.to_owned(),
+                    });
+                }
+                learned.schema(&measurement).tags.insert(tag.to_owned());
+            }
+
+            let mut coerced = Vec::new();
+            for (field, value) in point.fields() {
+                let actual = FieldType::of(value);
+                let expected = schema
+                    .and_then(|s| s.field_type(field))
+                    .or_else(|| learned.field_type(&measurement, field));
+
+                match expected {
+                    Some(expected) if expected == actual => {}
+                    Some(expected) => match coerce(value, expected).filter(|_| state.coerce) {
+                        Some(value) => coerced.push((field.to_owned(), value)),
+                        None => {
+                            return Err(SchemaError::FieldTypeConflict {
+                                point: index,
+                                measurement,
+                                field: field.to_owned(),
+                                expected,
+                                actual,
+                            })
+                        }
+                    },
+                    None if state.learn => {
+                        learned
+                            .schema(&measurement)
+                            .fields
+                            .insert(field.to_owned(), actual);
+                    }
+                    None => {
+                        return Err(SchemaError::UnknownField {
+                            point: index,
+                            measurement,
+                            field: field.to_owned(),
+                        })
+                    }
+                }
+            }
+
+            for (field, value) in coerced {
+                point.set_field(field, value);
+            }
+        }
+
+        Ok(learned)
+    }
+
+    /// Learn from a staged batch, declarations made in the meantime take precedence
+    pub(crate) fn commit(&self, learned: LearnedSchemas) {
+        let mut state = self.state();
+
+        for (name, learned) in learned.measurements {
+            let schema = state.measurements.entry(name).or_default();
+            schema.tags.extend(learned.tags);
+            for (field, field_type) in learned.fields {
+                schema.fields.entry(field).or_insert(field_type);
+            }
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Largest integer magnitude a float represents exactly
+const MAX_EXACT_FLOAT: u64 = 1 << f64::MANTISSA_DIGITS;
+
+/// `value` as `to`, if the conversion is exact
+fn coerce(value: &Value, to: FieldType) -> Option<Value> {
+    match (value, to) {
+        (Value::Integer(i), FieldType::Float) if i.unsigned_abs() <= MAX_EXACT_FLOAT => {
+            Some(Value::Float(*i as f64))
+        }
+        (Value::Integer(i), FieldType::UInteger) => u64::try_from(*i).ok().map(Value::UInteger),
+        (Value::UInteger(u), FieldType::Float) if *u <= MAX_EXACT_FLOAT => {
+            Some(Value::Float(*u as f64))
+        }
+        (Value::UInteger(u), FieldType::Integer) => i64::try_from(*u).ok().map(Value::Integer),
+        (Value::Float(f), FieldType::Integer)
+            if f.fract() == 0. && *f >= i64::MIN as f64 && *f < i64::MAX as f64 =>
+        {
+            Some(Value::Integer(*f as i64))
+        }
+        (Value::Float(f), FieldType::UInteger)
+            if f.fract() == 0. && *f >= 0. && *f < u64::MAX as f64 =>
+        {
+            Some(Value::UInteger(*f as u64))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema::{FieldType, MeasurementSchema, SchemaError, SchemaRegistry};
+    use crate::{DataPoint, DataPointBuilder, Value};
+
+    fn point(value: impl Into<Value>) -> DataPoint {
+        DataPointBuilder::new("cpu")
+            .with_tag("host", "a")
+            .with_field("value", value)
+            .into()
+    }
+
+    #[test]
+    fn declared_schema() {
+        let registry = SchemaRegistry::new().with_measurement(
+            "cpu",
+            MeasurementSchema::new()
+                .with_tag("host")
+                .with_field("value", FieldType::Float),
+        );
+
+        registry.check(&mut [point(0.5)]).unwrap();
+        assert_eq!(
+            Err(SchemaError::FieldTypeConflict {
+                point: 1,
+                measurement: "cpu".to_owned(),
+                field: "value".to_owned(),
+                expected: FieldType::Float,
+                actual: FieldType::Integer,
+            }),
+            registry.check(&mut [point(0.5), point(1i64)])
+        );
+        assert!(matches!(
+            registry.check(&mut [DataPointBuilder::new("mem").with_field("value", 0.).into()]),
+            Err(SchemaError::UnknownMeasurement { point: 0, .. })
+        ));
+        assert!(matches!(
+            registry.check(&mut [DataPointBuilder::new("cpu")
+                .with_tag("host", "a")
+                .with_tag("region", "eu")
+                .with_field("value", 0.5)
+                .into()]),
+            Err(SchemaError::UnknownTag { tag, .. }) if tag == "region"
+        ));
+    }
+
+    #[test]
+    fn learned_schema() {
+        let registry = SchemaRegistry::new().with_learning();
+
+        // the conflicting batch is rejected as a whole and nothing is learned from it
+        assert!(matches!(
+            registry.check(&mut [point(1i64), point(0.5)]),
+            Err(SchemaError::FieldTypeConflict { point: 1, .. })
+        ));
+        assert_eq!(None, registry.measurement("cpu"));
+
+        registry.check(&mut [point(0.5)]).unwrap();
+        assert_eq!(
+            Some(FieldType::Float),
+            registry.measurement("cpu").unwrap().field_type("value")
+        );
+        assert!(registry.check(&mut [point(true)]).is_err());
+    }
+
+    #[test]
+    fn coercion() {
+        let registry = SchemaRegistry::new()
+            .with_learning()
+            .with_coercion()
+            .with_measurement(
+                "cpu",
+                MeasurementSchema::new().with_field("value", FieldType::Float),
+            );
+
+        let mut points = [point(1i64)];
+        registry.check(&mut points).unwrap();
+        assert_eq!(Some(&Value::Float(1.)), points[0].field("value"));
+
+        assert!(registry.check(&mut [point(i64::MAX)]).is_err());
+        assert!(registry.check(&mut [point("1")]).is_err());
+    }
+}
@@ -0,0 +1,1104 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use chrono::DateTime;
+use serde::ser::{
+    self, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{ConversionError, DataPoint, DataPointBuilder, Timestamp, Value, WritePrecision};
+
+#[derive(Error, Debug)]
+pub enum SerializeError {
+    #[error("{0}")]
+    Custom(String),
+    #[error("Only structs and maps can be turned into points")]
+    NotAStruct,
+    #[error("Map keys must be strings, characters or numbers")]
+    InvalidKey,
+    #[error("Unsupported value for {0}")]
+    Unsupported(String),
+    #[error("Invalid timestamp {0}")]
+    InvalidTimestamp(String),
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+}
+
+impl ser::Error for SerializeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// Which keys of a serialized value become the tags and timestamp of a point
+///
+/// Nested structs and maps are flattened, their keys are joined with the separator, `.` unless
+/// configured otherwise. Tag and timestamp keys refer to the flattened names. Everything that is
+/// neither a tag nor the timestamp becomes a field, `None` and unit values are omitted.
+///
+/// Integer fields are written as signed integers whenever they fit into an `i64`, so a field does
+/// not change its type with the sign of the value. `serde_json` for example hands out
+/// non-negative numbers as unsigned, which would otherwise conflict with earlier negative ones.
+#[derive(Clone, Debug)]
+pub struct PointConfig {
+    measurement: String,
+    tags: HashSet<String>,
+    timestamp: Option<String>,
+    timestamp_precision: WritePrecision,
+    separator: String,
+    unsigned_integers: bool,
+}
+
+impl PointConfig {
+    pub fn new(measurement: impl Into<String>) -> Self {
+        Self {
+            measurement: measurement.into(),
+            tags: HashSet::new(),
+            timestamp: None,
+            timestamp_precision: WritePrecision::default(),
+            separator: ".".to_owned(),
+            unsigned_integers: false,
+        }
+    }
+
+    pub fn with_tag(mut self, key: impl Into<String>) -> Self {
+        self.tags.insert(key.into());
+        self
+    }
+
+    pub fn with_tags<K: Into<String>>(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.tags.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Take the timestamp from `key`, either an RFC 3339 string or an epoch integer
+    pub fn with_timestamp(mut self, key: impl Into<String>) -> Self {
+        self.timestamp = Some(key.into());
+        self
+    }
+
+    /// Unit of integer timestamps, nanoseconds by default
+    pub fn with_timestamp_precision(mut self, precision: WritePrecision) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Write unsigned integers as unsigned fields instead of converting them to signed ones
+    ///
+    /// Only useful if the serialized types are unsigned for every value, e.g. Rust structs with
+    /// `u32` fields, and the bucket should store them as unsigned.
+    pub fn with_unsigned_integers(mut self) -> Self {
+        self.unsigned_integers = true;
+        self
+    }
+}
+
+/// Turn any [Serialize] struct or map into a point, see [PointSerializer]
+///
+/// ```
+/// # use influx_write::serialize::{to_data_point, PointConfig};
+/// # use influx_write::Value;
+/// let payload = serde_json::json!({
+///     "device": "pump-1",
+///     "time": "2021-08-31T15:37:37Z",
+///     "readings": { "pressure": 1.5, "running": true },
+/// });
+///
+/// let config = PointConfig::new("pumps").with_tag("device").with_timestamp("time");
+/// let point = to_data_point(&payload, &config).unwrap();
+///
+/// assert_eq!(Some("pump-1"), point.tag("device"));
+/// assert_eq!(Some(&Value::Float(1.5)), point.field("readings.pressure"));
+/// ```
+pub fn to_data_point<T: Serialize + ?Sized>(
+    value: &T,
+    config: &PointConfig,
+) -> Result<DataPoint, SerializeError> {
+    value.serialize(PointSerializer::new(config))
+}
+
+/// Build a point from the flattened entries of a value
+fn build_point(
+    entries: Vec<(String, Value)>,
+    config: &PointConfig,
+) -> Result<DataPoint, SerializeError> {
+    let mut builder = DataPointBuilder::new(config.measurement.clone());
+    let mut fields = Vec::new();
+
+    for (key, value) in entries {
+        if config.tags.contains(&key) {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Float(f) => f.to_string(),
+                Value::Integer(i) => i.to_string(),
+                Value::UInteger(u) => u.to_string(),
+                Value::Boolean(b) => b.to_string(),
+            };
+            builder = builder.with_tag(key, value);
+        } else if config.timestamp.as_ref() == Some(&key) {
+            builder = builder.with_time(parse_timestamp(value, config.timestamp_precision)?);
+        } else {
+            fields.push((key, signed(value, config)));
+        }
+    }
+
+    let mut fields = fields.into_iter();
+    let Some((key, value)) = fields.next() else {
+        return Err(ConversionError::MissingField.into());
+    };
+
+    Ok(fields
+        .fold(builder.with_field(key, value), |builder, (key, value)| {
+            builder.with_field(key, value)
+        })
+        .into())
+}
+
+/// Unsigned integers that fit into an `i64` as signed ones, unless unsigned fields are configured
+fn signed(value: Value, config: &PointConfig) -> Value {
+    match value {
+        Value::UInteger(u) if !config.unsigned_integers => match i64::try_from(u) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::UInteger(u),
+        },
+        value => value,
+    }
+}
+
+fn parse_timestamp(value: Value, precision: WritePrecision) -> Result<Timestamp, SerializeError> {
+    let timestamp = match &value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s).ok().map(Timestamp::from),
+        Value::Integer(i) => Timestamp::from_line_protocol(*i, precision),
+        Value::UInteger(u) => i64::try_from(*u)
+            .ok()
+            .and_then(|i| Timestamp::from_line_protocol(i, precision)),
+        Value::Float(_) | Value::Boolean(_) => None,
+    };
+
+    timestamp.ok_or_else(|| SerializeError::InvalidTimestamp(format!("{value:?}")))
+}
+
+fn join(prefix: &str, separator: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{prefix}{separator}{key}")
+    }
+}
+
+/// serde [Serializer](ser::Serializer) turning a struct or map into a [DataPoint]
+///
+/// Used by [to_data_point], the serializer can also be handed to anything that drives a serde
+/// serializer itself. Values other than structs, maps and enum variants wrapping them fail with
+/// [SerializeError::NotAStruct].
+pub struct PointSerializer<'a> {
+    config: &'a PointConfig,
+}
+
+impl<'a> PointSerializer<'a> {
+    pub fn new(config: &'a PointConfig) -> Self {
+        Self { config }
+    }
+
+    fn compound(self, prefix: String) -> PointCompound<'a> {
+        PointCompound {
+            config: self.config,
+            prefix,
+            entries: Vec::new(),
+            index: 0,
+            key: None,
+        }
+    }
+}
+
+impl<'a> ser::Serializer for PointSerializer<'a> {
+    type Ok = DataPoint;
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<DataPoint, SerializeError>;
+    type SerializeTuple = Impossible<DataPoint, SerializeError>;
+    type SerializeTupleStruct = Impossible<DataPoint, SerializeError>;
+    type SerializeTupleVariant = PointCompound<'a>;
+    type SerializeMap = PointCompound<'a>;
+    type SerializeStruct = PointCompound<'a>;
+    type SerializeStructVariant = PointCompound<'a>;
+
+    fn serialize_bool(self, _v: bool) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_char(self, _v: char) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_none(self) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<DataPoint, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<DataPoint, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<DataPoint, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<DataPoint, SerializeError> {
+        let mut compound = self.compound(String::new());
+        compound.entry(variant, value)?;
+        compound.finish()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerializeError> {
+        Err(SerializeError::NotAStruct)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<PointCompound<'a>, SerializeError> {
+        Ok(self.compound(variant.to_owned()))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<PointCompound<'a>, SerializeError> {
+        Ok(self.compound(String::new()))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<PointCompound<'a>, SerializeError> {
+        Ok(self.compound(String::new()))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<PointCompound<'a>, SerializeError> {
+        Ok(self.compound(variant.to_owned()))
+    }
+}
+
+/// Collects the flattened entries of the top level value, the point is built when it ends
+pub struct PointCompound<'a> {
+    config: &'a PointConfig,
+    prefix: String,
+    entries: Vec<(String, Value)>,
+    index: usize,
+    key: Option<String>,
+}
+
+impl PointCompound<'_> {
+    fn entry<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerializeError> {
+        value.serialize(FlatSerializer {
+            prefix: join(&self.prefix, &self.config.separator, key),
+            separator: &self.config.separator,
+            out: &mut self.entries,
+        })
+    }
+
+    fn finish(self) -> Result<DataPoint, SerializeError> {
+        build_point(self.entries, self.config)
+    }
+}
+
+impl SerializeTupleVariant for PointCompound<'_> {
+    type Ok = DataPoint;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.index.to_string();
+        self.index += 1;
+        self.entry(&key, value)
+    }
+
+    fn end(self) -> Result<DataPoint, SerializeError> {
+        self.finish()
+    }
+}
+
+impl SerializeMap for PointCompound<'_> {
+    type Ok = DataPoint;
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.key.take().ok_or(SerializeError::InvalidKey)?;
+        self.entry(&key, value)
+    }
+
+    fn end(self) -> Result<DataPoint, SerializeError> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for PointCompound<'_> {
+    type Ok = DataPoint;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<DataPoint, SerializeError> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for PointCompound<'_> {
+    type Ok = DataPoint;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<DataPoint, SerializeError> {
+        self.finish()
+    }
+}
+
+/// Serializes one value, pushing every primitive it contains under its flattened key
+struct FlatSerializer<'a> {
+    prefix: String,
+    separator: &'a str,
+    out: &'a mut Vec<(String, Value)>,
+}
+
+impl<'a> FlatSerializer<'a> {
+    fn push(self, value: impl Into<Value>) -> Result<(), SerializeError> {
+        self.out.push((self.prefix, value.into()));
+        Ok(())
+    }
+
+    fn compound(self, prefix: String) -> Compound<'a> {
+        Compound {
+            prefix,
+            separator: self.separator,
+            out: self.out,
+            index: 0,
+            key: None,
+        }
+    }
+
+    fn sequence(self) -> Compound<'a> {
+        let prefix = self.prefix.clone();
+        self.compound(prefix)
+    }
+
+    fn variant(self, variant: &str) -> Compound<'a> {
+        let prefix = join(&self.prefix, self.separator, variant);
+        self.compound(prefix)
+    }
+}
+
+impl<'a> ser::Serializer for FlatSerializer<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerializeError> {
+        self.push(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerializeError> {
+        self.push(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerializeError> {
+        self.push(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerializeError> {
+        self.push(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerializeError> {
+        self.push(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), SerializeError> {
+        match i64::try_from(v) {
+            Ok(v) => self.push(v),
+            Err(_) => Err(SerializeError::Unsupported(self.prefix)),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerializeError> {
+        self.push(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerializeError> {
+        self.push(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerializeError> {
+        self.push(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerializeError> {
+        self.push(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), SerializeError> {
+        match u64::try_from(v) {
+            Ok(v) => self.push(v),
+            Err(_) => Err(SerializeError::Unsupported(self.prefix)),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SerializeError> {
+        self.push(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SerializeError> {
+        self.push(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SerializeError> {
+        self.push(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerializeError> {
+        self.push(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), SerializeError> {
+        Err(SerializeError::Unsupported(self.prefix))
+    }
+
+    fn serialize_none(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerializeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerializeError> {
+        self.push(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        value.serialize(FlatSerializer {
+            prefix: join(&self.prefix, self.separator, variant),
+            separator: self.separator,
+            out: self.out,
+        })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.sequence())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.sequence())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.sequence())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.variant(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, SerializeError> {
+        let prefix = self.prefix.clone();
+        Ok(self.compound(prefix))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        let prefix = self.prefix.clone();
+        Ok(self.compound(prefix))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.variant(variant))
+    }
+}
+
+/// Flattens the entries of structs, maps and sequences, sequence elements are keyed by index
+struct Compound<'a> {
+    prefix: String,
+    separator: &'a str,
+    out: &'a mut Vec<(String, Value)>,
+    index: usize,
+    key: Option<String>,
+}
+
+impl Compound<'_> {
+    fn entry<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerializeError> {
+        value.serialize(FlatSerializer {
+            prefix: join(&self.prefix, self.separator, key),
+            separator: self.separator,
+            out: self.out,
+        })
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        let key = self.index.to_string();
+        self.index += 1;
+        self.entry(&key, value)
+    }
+}
+
+impl SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.key.take().ok_or(SerializeError::InvalidKey)?;
+        self.entry(&key, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+/// Turns map keys into strings
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = SerializeError;
+    type SerializeSeq = Impossible<String, SerializeError>;
+    type SerializeTuple = Impossible<String, SerializeError>;
+    type SerializeTupleStruct = Impossible<String, SerializeError>;
+    type SerializeTupleVariant = Impossible<String, SerializeError>;
+    type SerializeMap = Impossible<String, SerializeError>;
+    type SerializeStruct = Impossible<String, SerializeError>;
+    type SerializeStructVariant = Impossible<String, SerializeError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, SerializeError> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_none(self) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerializeError> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerializeError> {
+        Err(SerializeError::InvalidKey)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use crate::serialize::{to_data_point, PointConfig, PointSerializer, SerializeError};
+    use crate::{ConversionError, Timestamp, Value, WritePrecision};
+
+    #[derive(Serialize)]
+    enum State {
+        Running,
+    }
+
+    #[derive(Serialize)]
+    struct Location {
+        site: &'static str,
+        rack: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Reading {
+        host: &'static str,
+        location: Location,
+        load: [f32; 2],
+        state: State,
+        offset: i16,
+        missing: Option<f64>,
+        time: u64,
+    }
+
+    #[test]
+    fn flatten_struct() {
+        let config = PointConfig::new("cpu")
+            .with_tags(["host", "location/rack"])
+            .with_timestamp("time")
+            .with_timestamp_precision(WritePrecision::S)
+            .with_separator("/");
+
+        let point = to_data_point(
+            &Reading {
+                host: "a",
+                location: Location {
+                    site: "fra",
+                    rack: 7,
+                },
+                load: [0.5, 1.0],
+                state: State::Running,
+                offset: -3,
+                missing: None,
+                time: 1630424257,
+            },
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!("cpu", point.measurement());
+        assert_eq!(Some("a"), point.tag("host"));
+        assert_eq!(Some("7"), point.tag("location/rack"));
+        assert_eq!(
+            Some(&Value::String("fra".to_owned())),
+            point.field("location/site")
+        );
+        assert_eq!(Some(&Value::Float(1.0)), point.field("load/1"));
+        assert_eq!(
+            Some(&Value::String("Running".to_owned())),
+            point.field("state")
+        );
+        assert_eq!(Some(&Value::Integer(-3)), point.field("offset"));
+        assert_eq!(None, point.field("missing"));
+        assert_eq!(Timestamp::from_secs(1630424257), point.time());
+    }
+
+    #[test]
+    fn invalid_values() {
+        let config = PointConfig::new("m").with_tag("tag");
+
+        assert!(matches!(
+            to_data_point(&1, &config),
+            Err(SerializeError::NotAStruct)
+        ));
+        assert!(matches!(
+            to_data_point(&BTreeMap::from([("tag", "only")]), &config),
+            Err(SerializeError::Conversion(ConversionError::MissingField))
+        ));
+        assert!(matches!(
+            to_data_point(&BTreeMap::from([((1, 2), 0)]), &config),
+            Err(SerializeError::InvalidKey)
+        ));
+        assert!(matches!(
+            to_data_point(
+                &BTreeMap::from([("time", "yesterday")]),
+                &config.with_timestamp("time")
+            ),
+            Err(SerializeError::InvalidTimestamp(_))
+        ));
+    }
+
+    #[test]
+    fn integer_signedness() {
+        let config = PointConfig::new("mqtt");
+        let point = |payload: serde_json::Value, config: &PointConfig| {
+            payload.serialize(PointSerializer::new(config)).unwrap()
+        };
+
+        // serde_json hands out 5 as unsigned and -1 as signed, both must be written alike
+        assert_eq!(
+            Some(&Value::Integer(5)),
+            point(serde_json::json!({ "level": 5 }), &config).field("level")
+        );
+        assert_eq!(
+            Some(&Value::Integer(-1)),
+            point(serde_json::json!({ "level": -1 }), &config).field("level")
+        );
+        assert_eq!(
+            Some(&Value::UInteger(u64::MAX)),
+            point(serde_json::json!({ "level": u64::MAX }), &config).field("level")
+        );
+
+        let config = config.with_unsigned_integers();
+        assert_eq!(
+            Some(&Value::UInteger(5)),
+            point(serde_json::json!({ "level": 5 }), &config).field("level")
+        );
+    }
+}
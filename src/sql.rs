@@ -0,0 +1,163 @@
+use http::{Method, Request};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+
+use crate::query::QueryError;
+use crate::InfluxWriter;
+
+pub const API_ENDPOINT_QUERY_SQL: &str = "/api/v3/query_sql";
+pub const API_ENDPOINT_QUERY_V1: &str = "/query";
+
+/// A result row as returned by the server, keyed by column name
+pub type Row = Map<String, serde_json::Value>;
+
+/// One series of an InfluxQL result
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Series {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Map<String, serde_json::Value>,
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub values: Vec<Vec<serde_json::Value>>,
+}
+
+impl Series {
+    /// The values as rows keyed by column name, the series tags are added to every row
+    pub fn rows(&self) -> impl Iterator<Item = Row> + '_ {
+        self.values.iter().map(|values| {
+            let mut row = self.tags.clone();
+            row.extend(self.columns.iter().cloned().zip(values.iter().cloned()));
+            row
+        })
+    }
+
+    /// Deserialize every row, see [Series::rows]
+    pub fn deserialize_rows<T: DeserializeOwned>(&self) -> Result<Vec<T>, QueryError> {
+        self.rows()
+            .map(|row| Ok(serde_json::from_value(row.into())?))
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct SqlRequest<'a> {
+    db: &'a str,
+    q: &'a str,
+    format: &'static str,
+}
+
+#[derive(Deserialize)]
+struct InfluxQlResponse {
+    #[serde(default)]
+    results: Vec<StatementResult>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatementResult {
+    #[serde(default)]
+    series: Vec<Series>,
+    error: Option<String>,
+}
+
+impl<W> InfluxWriter<W> {
+    /// SQL query against the writer's bucket, InfluxDB 3 calls it database
+    pub(crate) fn build_sql_request(&self, sql: &str) -> anyhow::Result<Request<String>> {
+        let body = serde_json::to_string(&SqlRequest {
+            db: &self.bucket,
+            q: sql,
+            format: "jsonl",
+        })?;
+
+        self.build_api_request(Method::POST, API_ENDPOINT_QUERY_SQL, &[], body)
+    }
+
+    /// InfluxQL query against the writer's bucket, InfluxDB 1.x calls it database
+    ///
+    /// Sent as POST so statements that modify data are accepted as well.
+    pub(crate) fn build_influxql_request(&self, influxql: &str) -> anyhow::Result<Request<String>> {
+        self.build_api_request(
+            Method::POST,
+            API_ENDPOINT_QUERY_V1,
+            &[("db", &self.bucket), ("q", influxql)],
+            String::new(),
+        )
+    }
+}
+
+/// Deserialize a JSON lines body, one row per line
+pub(crate) fn parse_jsonl<T: DeserializeOwned>(body: &[u8]) -> Result<Vec<T>, QueryError> {
+    body.split(|b| *b == b'\n')
+        .filter(|line| !line.trim_ascii().is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+/// Collect the series of all statements, failing on the first statement error
+pub(crate) fn parse_influxql(body: &[u8]) -> Result<Vec<Series>, QueryError> {
+    let response: InfluxQlResponse = serde_json::from_slice(body)?;
+    if let Some(error) = response.error {
+        return Err(QueryError::InfluxQl(error));
+    }
+
+    let mut series = Vec::new();
+    for result in response.results {
+        if let Some(error) = result.error {
+            return Err(QueryError::InfluxQl(error));
+        }
+        series.extend(result.series);
+    }
+
+    Ok(series)
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use crate::query::QueryError;
+    use crate::sql::{parse_influxql, parse_jsonl, Row};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Cpu {
+        host: String,
+        usage: f64,
+    }
+
+    #[test]
+    fn parse_sql_rows() {
+        let body = b"{\"host\":\"a\",\"usage\":0.5}\n{\"host\":\"b\",\"usage\":1.5}\n";
+
+        let rows: Vec<Row> = parse_jsonl(body).unwrap();
+        assert_eq!(Some("a"), rows[0]["host"].as_str());
+
+        let cpus: Vec<Cpu> = parse_jsonl(body).unwrap();
+        assert_eq!(
+            Cpu {
+                host: "b".to_owned(),
+                usage: 1.5
+            },
+            cpus[1]
+        );
+    }
+
+    #[test]
+    fn parse_influxql_series() {
+        let body = br#"{"results":[{"statement_id":0,"series":[{"name":"cpu","tags":{"host":"a"},"columns":["time","usage"],"values":[["2021-08-31T15:37:37Z",0.5],["2021-08-31T15:37:47Z",0.7]]}]}]}"#;
+
+        let series = parse_influxql(body).unwrap();
+        assert_eq!("cpu", series[0].name);
+
+        let cpus: Vec<Cpu> = series[0].deserialize_rows().unwrap();
+        assert_eq!(2, cpus.len());
+        assert_eq!("a", cpus[1].host);
+        assert_eq!(0.7, cpus[1].usage);
+
+        let error =
+            parse_influxql(br#"{"results":[{"statement_id":0,"error":"database not found: db"}]}"#)
+                .unwrap_err();
+        assert!(matches!(error, QueryError::InfluxQl(e) if e == "database not found: db"));
+    }
+}
@@ -0,0 +1,605 @@
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::header::{AUTHORIZATION, CONTENT_ENCODING, RETRY_AFTER};
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use url::Url;
+
+use crate::influx::parse_line_protocol;
+use crate::{
+    DataPoint, WritePrecision, API_ENDPOINT_HEALTH, API_ENDPOINT_PING, API_ENDPOINT_V2,
+    EMPTY_WRITE_MESSAGE,
+};
+
+pub const API_ENDPOINT_V1: &str = "/write";
+pub const API_ENDPOINT_V3: &str = "/api/v3/write_lp";
+
+/// Version reported by `/ping` and `/health`
+const VERSION: &str = "test-server";
+const BUILD: &str = "test";
+
+/// Local fake of the InfluxDB write endpoints for integration tests
+///
+/// Serves `/api/v2/write`, the 1.x compatible `/write` and `/api/v3/write_lp` as well as `/ping`
+/// and `/health` on a random port of `127.0.0.1`. Accepted writes are kept in memory and can be
+/// queried with [TestServer::points]. The server runs on its own thread and is shut down on drop,
+/// so it can be used from blocking as well as async tests.
+///
+/// ```
+/// # use influx_write::test_server::{Fault, TestServer};
+/// # fn main() -> std::io::Result<()> {
+/// let server = TestServer::builder()
+///     .with_token("token")
+///     .with_org("org")
+///     .with_bucket("bucket")
+///     .start()?;
+///
+/// // the next write is rejected, later ones are stored
+/// server.inject_fault(Fault::Status(http::StatusCode::SERVICE_UNAVAILABLE));
+///
+/// // ... write to server.url() ...
+///
+/// assert!(server.points("bucket").is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Configuration of a [TestServer], created with [TestServer::builder]
+#[derive(Default)]
+pub struct TestServerBuilder {
+    config: Config,
+}
+
+#[derive(Clone, Default)]
+struct Config {
+    token: Option<String>,
+    orgs: HashSet<String>,
+    buckets: HashSet<String>,
+    max_body_size: Option<usize>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    config: Config,
+    latency: Option<Duration>,
+    faults: VecDeque<Fault>,
+    writes: Vec<ReceivedWrite>,
+}
+
+/// Failure the [TestServer] answers the next request with instead of processing it
+#[derive(Clone, Debug)]
+pub enum Fault {
+    /// Respond with the given status code, e.g. `503 Service Unavailable`
+    Status(StatusCode),
+    /// Respond with `429 Too Many Requests` and a `Retry-After` header
+    TooManyRequests { retry_after: Duration },
+    /// Respond with `413 Payload Too Large`
+    PayloadTooLarge,
+    /// Delay this request before processing it normally
+    Latency(Duration),
+}
+
+/// A successful write stored by the [TestServer]
+#[derive(Clone, Debug)]
+pub struct ReceivedWrite {
+    pub endpoint: String,
+    pub org: Option<String>,
+    pub bucket: String,
+    pub precision: WritePrecision,
+    /// The decompressed line protocol body
+    pub body: String,
+}
+
+impl ReceivedWrite {
+    pub fn points(&self) -> Vec<DataPoint> {
+        parse_line_protocol(&self.body, self.precision)
+            .expect("stored bodies were validated when they were received")
+    }
+}
+
+impl TestServerBuilder {
+    /// Require this token, without a token every request is accepted
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.config.token = Some(token.into());
+        self
+    }
+
+    /// Accept writes for this org, without orgs every org is accepted
+    pub fn with_org(mut self, org: impl Into<String>) -> Self {
+        self.config.orgs.insert(org.into());
+        self
+    }
+
+    /// Accept writes to this bucket (or 1.x `db/rp`), without buckets every bucket is accepted
+    pub fn with_bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.config.buckets.insert(bucket.into());
+        self
+    }
+
+    /// Reject bodies larger than `max_body_size` bytes with `413 Payload Too Large`
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.config.max_body_size = Some(max_body_size);
+        self
+    }
+
+    pub fn start(self) -> io::Result<TestServer> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(ServerState {
+            config: self.config,
+            ..Default::default()
+        }));
+        let (shutdown, shutdown_rx) = oneshot::channel();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let thread_state = state.clone();
+        let thread = thread::spawn(move || {
+            runtime.block_on(serve(listener, thread_state, shutdown_rx));
+        });
+
+        Ok(TestServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl TestServer {
+    pub fn builder() -> TestServerBuilder {
+        TestServerBuilder::default()
+    }
+
+    /// Start a server accepting every token, org and bucket
+    pub fn start() -> io::Result<Self> {
+        Self::builder().start()
+    }
+
+    /// Base url to pass to [crate::InfluxWriter]
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).expect("socket address is a valid url")
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Answer the next unfaulted request with `fault`, faults are applied in order
+    pub fn inject_fault(&self, fault: Fault) {
+        self.state().faults.push_back(fault);
+    }
+
+    /// Delay every request by `latency`
+    pub fn set_latency(&self, latency: Option<Duration>) {
+        self.state().latency = latency;
+    }
+
+    pub fn writes(&self) -> Vec<ReceivedWrite> {
+        self.state().writes.clone()
+    }
+
+    /// All points written to `bucket`, in order
+    pub fn points(&self, bucket: &str) -> Vec<DataPoint> {
+        self.writes()
+            .iter()
+            .filter(|write| write.bucket == bucket)
+            .flat_map(ReceivedWrite::points)
+            .collect()
+    }
+
+    /// Forget all stored writes and pending faults
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.writes.clear();
+        state.faults.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn lock(state: &Mutex<ServerState>) -> MutexGuard<'_, ServerState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+async fn serve(
+    listener: std::net::TcpListener,
+    state: Arc<Mutex<ServerState>>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let Ok(listener) = TcpListener::from_std(listener) else {
+        return;
+    };
+
+    loop {
+        let stream = tokio::select! {
+            _ = &mut shutdown => return,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(_) => continue,
+            },
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(state.clone(), req));
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+/// Api flavour of a write endpoint, determines parameter names and error format
+#[derive(Copy, Clone)]
+enum Api {
+    V1,
+    V2,
+    V3,
+}
+
+async fn handle(
+    state: Arc<Mutex<ServerState>>,
+    req: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let (api, method) = match req.uri().path() {
+        API_ENDPOINT_V1 => (Api::V1, Method::POST),
+        API_ENDPOINT_V2 => (Api::V2, Method::POST),
+        API_ENDPOINT_V3 => (Api::V3, Method::POST),
+        API_ENDPOINT_PING | API_ENDPOINT_HEALTH => (Api::V2, Method::GET),
+        _ => {
+            return Ok(error(
+                Api::V2,
+                StatusCode::NOT_FOUND,
+                "not found",
+                "path not found",
+            ))
+        }
+    };
+    if req.method() != method {
+        return Ok(error(
+            api,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed",
+            &format!("only {method} is supported"),
+        ));
+    }
+
+    let (latency, fault, config) = {
+        let mut state = lock(&state);
+        (
+            state.latency,
+            state.faults.pop_front(),
+            state.config.clone(),
+        )
+    };
+
+    if let Some(latency) = latency {
+        tokio::time::sleep(latency).await;
+    }
+    match fault {
+        None => {}
+        Some(Fault::Latency(latency)) => tokio::time::sleep(latency).await,
+        Some(Fault::Status(status)) => {
+            return Ok(error(api, status, "internal error", "injected fault"));
+        }
+        Some(Fault::TooManyRequests { retry_after }) => {
+            let mut response = error(
+                api,
+                StatusCode::TOO_MANY_REQUESTS,
+                "too many requests",
+                "injected fault",
+            );
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.as_secs().into());
+            return Ok(response);
+        }
+        Some(Fault::PayloadTooLarge) => return Ok(payload_too_large(api)),
+    }
+
+    match req.uri().path() {
+        API_ENDPOINT_PING => {
+            let mut response = response(StatusCode::NO_CONTENT, Bytes::new());
+            let headers = response.headers_mut();
+            headers.insert("x-influxdb-version", VERSION.parse().unwrap());
+            headers.insert("x-influxdb-build", BUILD.parse().unwrap());
+            return Ok(response);
+        }
+        API_ENDPOINT_HEALTH => {
+            return Ok(response(
+                StatusCode::OK,
+                Bytes::from(
+                    serde_json::json!({
+                        "name": "influxdb",
+                        "message": "ready for queries and writes",
+                        "status": "pass",
+                        "checks": [],
+                        "version": VERSION,
+                        "commit": BUILD,
+                    })
+                    .to_string(),
+                ),
+            ));
+        }
+        _ => {}
+    }
+
+    let (parts, body) = req.into_parts();
+    let params: Vec<(String, String)> = parts
+        .uri
+        .query()
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    if let Some(token) = &config.token {
+        if !authorized(api, &parts.headers, &param, token) {
+            return Ok(error(
+                api,
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "unauthorized access",
+            ));
+        }
+    }
+
+    let (org, bucket) = match api {
+        Api::V1 => (
+            None,
+            param("db").map(|db| match param("rp") {
+                Some(rp) => format!("{db}/{rp}"),
+                None => db,
+            }),
+        ),
+        Api::V2 => (param("org").or_else(|| param("orgID")), param("bucket")),
+        Api::V3 => (None, param("db")),
+    };
+
+    if let Api::V2 = api {
+        match &org {
+            None => {
+                return Ok(error(
+                    api,
+                    StatusCode::BAD_REQUEST,
+                    "invalid",
+                    "missing org",
+                ))
+            }
+            Some(org) if !config.orgs.is_empty() && !config.orgs.contains(org) => {
+                return Ok(error(
+                    api,
+                    StatusCode::NOT_FOUND,
+                    "not found",
+                    &format!("organization name \"{org}\" not found"),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    let bucket = match bucket {
+        None => {
+            return Ok(error(
+                api,
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                "missing bucket",
+            ))
+        }
+        Some(bucket) if !config.buckets.is_empty() && !config.buckets.contains(&bucket) => {
+            return Ok(error(
+                api,
+                StatusCode::NOT_FOUND,
+                "not found",
+                &format!("bucket \"{bucket}\" not found"),
+            ))
+        }
+        Some(bucket) => bucket,
+    };
+
+    let precision = match parse_precision(api, param("precision").as_deref()) {
+        Some(precision) => precision,
+        None => {
+            return Ok(error(
+                api,
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                "invalid precision",
+            ))
+        }
+    };
+
+    let body = match body.collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) => {
+            return Ok(error(
+                api,
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                &e.to_string(),
+            ))
+        }
+    };
+    if config.max_body_size.is_some_and(|max| body.len() > max) {
+        return Ok(payload_too_large(api));
+    }
+
+    let gzip = parts
+        .headers
+        .get(CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding == "gzip");
+    let body = match decode_body(&body, gzip) {
+        Ok(body) => body,
+        Err(e) => {
+            return Ok(error(
+                api,
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                &e.to_string(),
+            ))
+        }
+    };
+
+    match parse_line_protocol(&body, precision) {
+        Err(e) => {
+            return Ok(error(
+                api,
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                &e.to_string(),
+            ))
+        }
+        // like InfluxDB, only after the permissions were checked
+        Ok(points) if points.is_empty() => {
+            return Ok(error(
+                api,
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                EMPTY_WRITE_MESSAGE,
+            ))
+        }
+        Ok(_) => {}
+    }
+
+    lock(&state).writes.push(ReceivedWrite {
+        endpoint: parts.uri.path().to_owned(),
+        org,
+        bucket,
+        precision,
+        body,
+    });
+
+    Ok(response(StatusCode::NO_CONTENT, Bytes::new()))
+}
+
+fn authorized(
+    api: Api,
+    headers: &HeaderMap,
+    param: &impl Fn(&str) -> Option<String>,
+    token: &str,
+) -> bool {
+    let header = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok());
+
+    match header {
+        Some(header) => match header.split_once(' ') {
+            Some(("Token", value)) => value == token,
+            Some(("Bearer", value)) => matches!(api, Api::V3) && value == token,
+            _ => false,
+        },
+        // 1.x clients may authenticate with query parameters, the token is used as password
+        None => matches!(api, Api::V1) && param("p").as_deref() == Some(token),
+    }
+}
+
+fn parse_precision(api: Api, precision: Option<&str>) -> Option<WritePrecision> {
+    let Some(precision) = precision else {
+        return Some(WritePrecision::NS);
+    };
+
+    match api {
+        Api::V1 => match precision {
+            "n" | "ns" => Some(WritePrecision::NS),
+            "u" | "us" => Some(WritePrecision::US),
+            "ms" => Some(WritePrecision::MS),
+            "s" => Some(WritePrecision::S),
+            _ => None,
+        },
+        Api::V2 => precision.parse().ok(),
+        Api::V3 => match precision {
+            "auto" | "nanosecond" => Some(WritePrecision::NS),
+            "microsecond" => Some(WritePrecision::US),
+            "millisecond" => Some(WritePrecision::MS),
+            "second" => Some(WritePrecision::S),
+            _ => None,
+        },
+    }
+}
+
+fn decode_body(body: &[u8], gzip: bool) -> io::Result<String> {
+    let mut decoded = String::new();
+
+    if gzip {
+        flate2::read::MultiGzDecoder::new(body).read_to_string(&mut decoded)?;
+    } else {
+        decoded = String::from_utf8(body.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    Ok(decoded)
+}
+
+fn payload_too_large(api: Api) -> Response<Full<Bytes>> {
+    error(
+        api,
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "request too large",
+        "unable to read data: request body too large",
+    )
+}
+
+fn error(api: Api, status: StatusCode, code: &str, message: &str) -> Response<Full<Bytes>> {
+    let body = match api {
+        Api::V1 => serde_json::json!({ "error": message }),
+        Api::V2 | Api::V3 => serde_json::json!({ "code": code, "message": message }),
+    };
+
+    let mut response = response(status, Bytes::from(body.to_string()));
+    response.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn response(status: StatusCode, body: Bytes) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body));
+    *response.status_mut() = status;
+    response
+}
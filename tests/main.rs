@@ -1,13 +1,35 @@
 use std::vec;
 
+#[cfg(any(
+    feature = "reqwest",
+    feature = "reqwest-blocking",
+    feature = "ureq",
+    feature = "hyper"
+))]
 use mockito::Matcher;
 
-use influx_write::{Authorization, DataPointBuilder};
+use influx_write::recording::RecordingClient;
+use influx_write::{Authorization, DataPointBuilder, InfluxWriter};
 
 const MOCK_ORG: &str = "MyOrg";
 const MOCK_BUCKET: &str = "MyBucket";
 const MOCK_TOKEN: &str = "djw9r30ur9093ur";
 
+/// A writer for [MOCK_ORG] and [MOCK_BUCKET] recording its requests into the returned client
+fn recording_writer() -> (RecordingClient, InfluxWriter<RecordingClient>) {
+    let client = RecordingClient::new();
+    let writer = InfluxWriter::new_with_blocking_client(
+        client.clone(),
+        "http://localhost:8086".parse().unwrap(),
+        Authorization::token(MOCK_TOKEN).unwrap(),
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )
+    .unwrap();
+
+    (client, writer)
+}
+
 #[cfg(feature = "reqwest-blocking")]
 #[test]
 fn test_reqwest_blocking() -> anyhow::Result<()> {
@@ -44,6 +66,72 @@ fn test_reqwest_blocking() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "reqwest-blocking")]
+#[test]
+fn test_reqwest_blocking_error_status() -> anyhow::Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", influx_write::API_ENDPOINT_V2)
+        .match_query(Matcher::Any)
+        .with_status(401)
+        .with_body(r#"{"code":"unauthorized","message":"unauthorized access"}"#)
+        .create();
+
+    let mut client =
+        influx_write::InfluxWriter::<influx_write::blocking::reqwest::ReqwestClient>::new(
+            server.url().parse()?,
+            Authorization::token(MOCK_TOKEN)?,
+            MOCK_ORG,
+            MOCK_BUCKET,
+        )?;
+
+    let result = client.write_single_blocking(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into(),
+    );
+
+    mock.assert();
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "ureq")]
+#[test]
+fn test_ureq_blocking() -> anyhow::Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", influx_write::API_ENDPOINT_V2)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("org".into(), MOCK_ORG.into()),
+            Matcher::UrlEncoded("bucket".into(), MOCK_BUCKET.into()),
+        ]))
+        .match_header(
+            "authorization",
+            Matcher::Exact(format!("Token {MOCK_TOKEN}")),
+        )
+        .match_body(Matcher::Exact("measurement field=0".into()))
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::blocking::ureq::UreqClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    client.write_single_blocking(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into(),
+    )?;
+
+    mock.assert();
+
+    Ok(())
+}
+
 #[cfg(feature = "reqwest")]
 #[tokio::test]
 async fn test_reqwest_async() -> anyhow::Result<()> {
@@ -80,3 +168,1598 @@ async fn test_reqwest_async() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_reqwest_async_error_status() -> anyhow::Result<()> {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", influx_write::API_ENDPOINT_V2)
+        .match_query(Matcher::Any)
+        .with_status(401)
+        .with_body(r#"{"code":"unauthorized","message":"unauthorized access"}"#)
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let result = client
+        .write_single(
+            DataPointBuilder::new("measurement")
+                .with_field("field", 0.)
+                .into(),
+        )
+        .await;
+
+    mock.assert();
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "hyper")]
+#[tokio::test]
+async fn test_hyper_async() -> anyhow::Result<()> {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", influx_write::API_ENDPOINT_V2)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("org".into(), MOCK_ORG.into()),
+            Matcher::UrlEncoded("bucket".into(), MOCK_BUCKET.into()),
+        ]))
+        .match_header(
+            "authorization",
+            Matcher::Exact(format!("Token {MOCK_TOKEN}")),
+        )
+        .match_body(Matcher::Exact("measurement field=0".into()))
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::hyper::HyperClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    client
+        .write_single(
+            DataPointBuilder::new("measurement")
+                .with_field("field", 0.)
+                .into(),
+        )
+        .await?;
+
+    mock.assert();
+
+    Ok(())
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_tower_service() -> anyhow::Result<()> {
+    use tower::{service_fn, ServiceExt};
+
+    let service = service_fn(|req: http::Request<String>| async move {
+        assert_eq!(
+            Some(&format!("Token {MOCK_TOKEN}").parse::<http::HeaderValue>()?),
+            req.headers().get("authorization")
+        );
+        assert_eq!("measurement field=0", req.body());
+
+        http::Response::builder()
+            .status(http::StatusCode::NO_CONTENT)
+            .body(String::new())
+            .map_err(anyhow::Error::from)
+    });
+
+    let writer = influx_write::InfluxWriter::new_with_client(
+        influx_write::tower::TowerClient::new(service),
+        "http://localhost:8086".parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    writer
+        .oneshot(vec![DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into()])
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_tower_service_error() -> anyhow::Result<()> {
+    use tower::{service_fn, ServiceExt};
+
+    let service = service_fn(|_: http::Request<String>| async move {
+        Err::<http::Response<String>, _>(anyhow::anyhow!("connection refused").context("connect"))
+    });
+
+    let writer = influx_write::InfluxWriter::new_with_client(
+        influx_write::tower::TowerClient::new(service),
+        "http://localhost:8086".parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let error = writer
+        .oneshot(vec![DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into()])
+        .await
+        .unwrap_err();
+
+    // the source chain of the service error is kept
+    assert_eq!("connect", error.to_string());
+    assert_eq!("connection refused", error.root_cause().to_string());
+
+    Ok(())
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_tower_client_timestamps_with_retries() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use chrono::DateTime;
+    use influx_write::tower::{ClientTimestampLayer, TowerClient};
+    use influx_write::{DataPoint, Timestamp};
+    use tower::retry::{Policy, RetryLayer};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    /// Retries a failed write once
+    #[derive(Clone)]
+    struct RetryOnce {
+        retried: bool,
+    }
+
+    impl<E> Policy<Vec<DataPoint>, (), E> for RetryOnce {
+        type Future = std::future::Ready<()>;
+
+        fn retry(
+            &mut self,
+            _: &mut Vec<DataPoint>,
+            result: &mut Result<(), E>,
+        ) -> Option<Self::Future> {
+            if result.is_ok() || self.retried {
+                return None;
+            }
+            self.retried = true;
+            Some(std::future::ready(()))
+        }
+
+        fn clone_request(&mut self, points: &Vec<DataPoint>) -> Option<Vec<DataPoint>> {
+            Some(points.clone())
+        }
+    }
+
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let service = service_fn({
+        let bodies = bodies.clone();
+        move |req: http::Request<String>| {
+            let bodies = bodies.clone();
+            async move {
+                let mut bodies = bodies.lock().unwrap();
+                bodies.push(req.body().clone());
+                // the first attempt fails
+                let status = match bodies.len() {
+                    1 => http::StatusCode::SERVICE_UNAVAILABLE,
+                    _ => http::StatusCode::NO_CONTENT,
+                };
+                http::Response::builder()
+                    .status(status)
+                    .body(String::new())
+                    .map_err(anyhow::Error::from)
+            }
+        }
+    });
+
+    // every reading of the clock is a second later than the one before
+    let seconds = Arc::new(AtomicI64::new(1630424257));
+    let clock = move || {
+        let seconds = seconds.fetch_add(1, Ordering::SeqCst);
+        Timestamp::from(DateTime::from_timestamp(seconds, 0).unwrap())
+    };
+
+    let writer = InfluxWriter::new_with_client(
+        TowerClient::new(service),
+        "http://localhost:8086".parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+    let service = ServiceBuilder::new()
+        .layer(ClientTimestampLayer::new(clock))
+        .layer(RetryLayer::new(RetryOnce { retried: false }))
+        .service(writer);
+
+    service
+        .oneshot(vec![DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into()])
+        .await?;
+
+    // both attempts send the time the write was accepted
+    assert_eq!(
+        vec![
+            "measurement field=0 1630424257000000000",
+            "measurement field=0 1630424257000000000"
+        ],
+        *bodies.lock().unwrap()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_udp_blocking() -> anyhow::Result<()> {
+    let server = std::net::UdpSocket::bind("127.0.0.1:0")?;
+
+    let mut writer = influx_write::blocking::udp::UdpWriter::connect(server.local_addr()?)?
+        .with_max_datagram_size(20);
+
+    writer.write((0..3).map(|i| {
+        DataPointBuilder::new("measurement")
+            .with_field("field", i as i64)
+            .into()
+    }))?;
+
+    let mut buf = [0; 64];
+    for expected in [
+        "measurement field=0i",
+        "measurement field=1i",
+        "measurement field=2i",
+    ] {
+        let len = server.recv(&mut buf)?;
+        assert_eq!(expected.as_bytes(), &buf[..len]);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_udp_async() -> anyhow::Result<()> {
+    let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+
+    let mut writer = influx_write::udp::AsyncUdpWriter::connect(server.local_addr()?).await?;
+
+    writer
+        .write((0..2).map(|i| {
+            DataPointBuilder::new("measurement")
+                .with_field("field", i as i64)
+                .into()
+        }))
+        .await?;
+
+    let mut buf = [0; 64];
+    let len = server.recv(&mut buf).await?;
+    assert_eq!(b"measurement field=0i\nmeasurement field=1i", &buf[..len]);
+
+    Ok(())
+}
+
+#[test]
+fn test_tcp_blocking() -> anyhow::Result<()> {
+    use std::io::Read;
+
+    use influx_write::blocking::stream::{TcpConnector, TcpWriter};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+
+    let mut writer = TcpWriter::new(TcpConnector::new(listener.local_addr()?)?);
+    writer.write((0..2).map(|i| {
+        DataPointBuilder::new("measurement")
+            .with_field("field", i as i64)
+            .into()
+    }))?;
+    drop(writer);
+
+    let mut received = String::new();
+    listener.accept()?.0.read_to_string(&mut received)?;
+    assert_eq!("measurement field=0i\nmeasurement field=1i\n", received);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_blocking() -> anyhow::Result<()> {
+    use std::io::Read;
+
+    use influx_write::blocking::stream::{UnixConnector, UnixWriter};
+
+    let path = std::env::temp_dir().join(format!("influx-write-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path)?;
+
+    let mut writer = UnixWriter::new(UnixConnector::new(&path));
+    writer.write_single(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into(),
+    )?;
+    drop(writer);
+
+    let mut received = String::new();
+    listener.accept()?.0.read_to_string(&mut received)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!("measurement field=0\n", received);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_tcp_async() -> anyhow::Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    use influx_write::blocking::stream::TcpConnector;
+    use influx_write::stream::AsyncTcpWriter;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+
+    let mut writer = AsyncTcpWriter::new(TcpConnector::new(listener.local_addr()?)?);
+    writer
+        .write_single(
+            DataPointBuilder::new("measurement")
+                .with_field("field", 0.)
+                .into(),
+        )
+        .await?;
+    drop(writer);
+
+    let mut received = String::new();
+    listener
+        .accept()
+        .await?
+        .0
+        .read_to_string(&mut received)
+        .await?;
+    assert_eq!("measurement field=0\n", received);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_tcp_async_write_timeout() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use influx_write::blocking::stream::TcpConnector;
+    use influx_write::stream::AsyncTcpWriter;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+
+    let mut writer = AsyncTcpWriter::new(
+        TcpConnector::new(listener.local_addr()?)?.with_write_timeout(Duration::from_millis(100)),
+    );
+
+    // the peer accepts the connection but never reads, so the socket buffers fill up
+    let points = (0..200_000).map(|i| {
+        DataPointBuilder::new("measurement")
+            .with_tag("tag", "a".repeat(64))
+            .with_field("field", i)
+            .into()
+    });
+    let result = tokio::time::timeout(Duration::from_secs(10), writer.write(points)).await?;
+
+    let error = result.unwrap_err();
+    assert_eq!(
+        Some(std::io::ErrorKind::TimedOut),
+        error.downcast_ref::<std::io::Error>().map(|e| e.kind())
+    );
+    drop(listener);
+
+    Ok(())
+}
+
+#[test]
+fn test_io_writer() -> anyhow::Result<()> {
+    let mut writer = influx_write::blocking::file::IoWriter::new(Vec::new());
+
+    writer.write_single(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .into(),
+    )?;
+    writer.write_single(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 1.)
+            .into(),
+    )?;
+
+    assert_eq!(
+        b"measurement field=0\nmeasurement field=1\n",
+        writer.into_inner().as_slice()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_file_writer_rotation() -> anyhow::Result<()> {
+    use influx_write::blocking::file::{FileWriter, Rotation};
+
+    let dir = std::env::temp_dir().join(format!("influx-write-rotation-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+
+    let mut writer = FileWriter::new(dir.join("points.lp")).with_rotation(Rotation {
+        max_size: Some(20),
+        max_age: None,
+    });
+    for i in 0..3 {
+        writer.write_single(
+            DataPointBuilder::new("measurement")
+                .with_field("field", i as i64)
+                .into(),
+        )?;
+    }
+    drop(writer);
+
+    let mut files = std::fs::read_dir(&dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.sort();
+    let contents = files
+        .iter()
+        .map(std::fs::read_to_string)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    std::fs::remove_dir_all(&dir)?;
+
+    // the active file sorts first, followed by the rotated files in order of rotation
+    assert_eq!(
+        vec![
+            "measurement field=2i\n",
+            "measurement field=0i\n",
+            "measurement field=1i\n"
+        ],
+        contents
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_file_writer_gzip() -> anyhow::Result<()> {
+    use std::io::Read;
+
+    let path = std::env::temp_dir().join(format!("influx-write-{}.lp.gz", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    for i in 0..2 {
+        let mut writer = influx_write::blocking::file::FileWriter::new(&path)
+            .with_gzip(flate2::Compression::default());
+        writer.write_single(
+            DataPointBuilder::new("measurement")
+                .with_field("field", i as i64)
+                .into(),
+        )?;
+    }
+
+    let mut received = String::new();
+    flate2::read::MultiGzDecoder::new(std::fs::File::open(&path)?).read_to_string(&mut received)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!("measurement field=0i\nmeasurement field=1i\n", received);
+
+    Ok(())
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_file_writer_gzip_rotation() -> anyhow::Result<()> {
+    use std::io::Read;
+
+    use influx_write::blocking::file::{FileWriter, Rotation};
+
+    let dir =
+        std::env::temp_dir().join(format!("influx-write-gzip-rotation-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+
+    // the size limit counts bytes on disk, so it also applies to the file found after a restart
+    for batch in [0..2, 2..3] {
+        let mut writer = FileWriter::new(dir.join("points.lp.gz"))
+            .with_gzip(flate2::Compression::default())
+            .with_rotation(Rotation {
+                max_size: Some(1),
+                max_age: None,
+            });
+        for i in batch {
+            writer.write_single(
+                DataPointBuilder::new("measurement")
+                    .with_field("field", i as i64)
+                    .into(),
+            )?;
+        }
+    }
+
+    let mut files = std::fs::read_dir(&dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.sort();
+    let contents = files
+        .iter()
+        .map(|path| {
+            let mut content = String::new();
+            flate2::read::MultiGzDecoder::new(std::fs::File::open(path)?)
+                .read_to_string(&mut content)?;
+            Ok(content)
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    std::fs::remove_dir_all(&dir)?;
+
+    assert!(files
+        .iter()
+        .all(|path| path.extension() == Some("gz".as_ref())));
+    // the rotated files sort first in order of rotation, followed by the active file
+    assert_eq!(
+        vec![
+            "measurement field=0i\n",
+            "measurement field=1i\n",
+            "measurement field=2i\n"
+        ],
+        contents
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recording_client() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use influx_write::delete::DeletePredicate;
+    use influx_write::recording::ScriptedResponse;
+    use influx_write::Timestamp;
+
+    let client = RecordingClient::new();
+    client
+        .respond_with(
+            ScriptedResponse::status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                .with_body("boom")
+                .with_delay(Duration::from_millis(10)),
+        )
+        .respond_with(ScriptedResponse::error("connection reset"));
+
+    let mut writer = influx_write::InfluxWriter::new_with_client(
+        client.clone(),
+        "http://localhost:8086".parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let point = || {
+        DataPointBuilder::new("measurement")
+            .with_tag("sensor", "a")
+            .with_field("field", 0.)
+            .into()
+    };
+
+    assert!(writer.write_single(point()).await.is_err());
+    assert!(writer.write_single(point()).await.is_err());
+    writer.write_single(point()).await?;
+
+    assert_eq!(3, client.request_count());
+    assert_eq!(Some(MOCK_ORG.to_owned()), client.requests()[0].org());
+    assert_eq!(3, client.points(MOCK_BUCKET)?.len());
+    client.assert_point(MOCK_BUCKET, "measurement", &[("sensor", "a")]);
+    assert!(!client.received_point(MOCK_BUCKET, "measurement", &[("sensor", "b")])?);
+    assert!(!client.received_point("other", "measurement", &[])?);
+
+    // other requests to the bucket, like deletes, are not taken for writes
+    writer
+        .delete(
+            Timestamp::from_nanos(0),
+            Timestamp::from_nanos(1),
+            &DeletePredicate::new().measurement("measurement"),
+        )
+        .await?;
+    assert_eq!(3, client.points(MOCK_BUCKET)?.len());
+    client.assert_point(MOCK_BUCKET, "measurement", &[("sensor", "a")]);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "test-server", feature = "reqwest"))]
+#[tokio::test]
+async fn test_test_server() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use influx_write::test_server::{Fault, TestServer};
+
+    let server = TestServer::builder()
+        .with_token(MOCK_TOKEN)
+        .with_org(MOCK_ORG)
+        .with_bucket(MOCK_BUCKET)
+        .start()?;
+    let point = || {
+        DataPointBuilder::new("measurement")
+            .with_tag("sensor", "a")
+            .with_field("field", 0.)
+            .into()
+    };
+
+    let mut writer = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url(),
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+    server.inject_fault(Fault::TooManyRequests {
+        retry_after: Duration::from_secs(1),
+    });
+    assert!(writer.write_single(point()).await.is_err());
+    writer.write_single(point()).await?;
+
+    let mut unauthorized = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url(),
+        Authorization::token("wrong")?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+    assert!(unauthorized.write_single(point()).await.is_err());
+
+    let mut missing_bucket =
+        influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+            server.url(),
+            Authorization::token(MOCK_TOKEN)?,
+            MOCK_ORG,
+            "missing",
+        )?;
+    assert!(missing_bucket.write_single(point()).await.is_err());
+
+    assert_eq!(1, server.writes().len());
+    assert_eq!(1, server.points(MOCK_BUCKET).len());
+
+    Ok(())
+}
+
+#[cfg(all(feature = "test-server", feature = "reqwest"))]
+#[tokio::test]
+async fn test_test_server_endpoints() -> anyhow::Result<()> {
+    use std::io::Write;
+
+    use influx_write::test_server::{TestServer, API_ENDPOINT_V1, API_ENDPOINT_V3};
+
+    let server = TestServer::builder().with_token(MOCK_TOKEN).start()?;
+    let client = reqwest::Client::new();
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(b"v2 field=1i 1")?;
+    let response = client
+        .post(server.url().join(influx_write::API_ENDPOINT_V2)?)
+        .query(&[("org", MOCK_ORG), ("bucket", "v2"), ("precision", "s")])
+        .header("Authorization", format!("Token {MOCK_TOKEN}"))
+        .header("Content-Encoding", "gzip")
+        .body(gzip.finish()?)
+        .send()
+        .await?;
+    assert_eq!(http::StatusCode::NO_CONTENT, response.status());
+
+    let response = client
+        .post(server.url().join(API_ENDPOINT_V1)?)
+        .query(&[("db", "v1"), ("rp", "autogen"), ("p", MOCK_TOKEN)])
+        .body("v1 field=1i")
+        .send()
+        .await?;
+    assert_eq!(http::StatusCode::NO_CONTENT, response.status());
+
+    let response = client
+        .post(server.url().join(API_ENDPOINT_V3)?)
+        .query(&[("db", "v3"), ("precision", "second")])
+        .header("Authorization", format!("Bearer {MOCK_TOKEN}"))
+        .body("v3 field=")
+        .send()
+        .await?;
+    assert_eq!(http::StatusCode::BAD_REQUEST, response.status());
+
+    // like InfluxDB, a write without points is rejected
+    let response = client
+        .post(server.url().join(influx_write::API_ENDPOINT_V2)?)
+        .query(&[("org", MOCK_ORG), ("bucket", MOCK_BUCKET)])
+        .header("Authorization", format!("Token {MOCK_TOKEN}"))
+        .send()
+        .await?;
+    assert_eq!(http::StatusCode::BAD_REQUEST, response.status());
+    let body: serde_json::Value = serde_json::from_slice(&response.bytes().await?)?;
+    assert_eq!("writing requires points", body["message"]);
+
+    let writes = server.writes();
+    assert_eq!(2, writes.len());
+    assert_eq!("v2 field=1i 1", writes[0].body);
+    assert_eq!(influx_write::WritePrecision::S, writes[0].precision);
+    assert_eq!("v1/autogen", writes[1].bucket);
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_ping_and_health() -> anyhow::Result<()> {
+    let mut server = mockito::Server::new_async().await;
+    let ping = server
+        .mock("GET", influx_write::API_ENDPOINT_PING)
+        .match_header(
+            "authorization",
+            Matcher::Exact(format!("Token {MOCK_TOKEN}")),
+        )
+        .with_status(204)
+        .with_header("X-Influxdb-Version", "v2.7.1")
+        .with_header("X-Influxdb-Build", "OSS")
+        .create();
+    let health = server
+        .mock("GET", influx_write::API_ENDPOINT_HEALTH)
+        .with_status(503)
+        .with_body(r#"{"name":"influxdb","message":"not ready","status":"fail","checks":[]}"#)
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    assert_eq!(
+        influx_write::Ping {
+            version: Some("v2.7.1".to_owned()),
+            build: Some("OSS".to_owned()),
+        },
+        client.ping().await?
+    );
+
+    let status = client.health().await?;
+    assert_eq!(influx_write::HealthStatus::Fail, status.status);
+    assert_eq!(Some("not ready".to_owned()), status.message);
+
+    ping.assert();
+    health.assert();
+
+    Ok(())
+}
+
+#[test]
+fn test_ping_and_health_blocking() -> anyhow::Result<()> {
+    use influx_write::recording::ScriptedResponse;
+
+    let (client, mut writer) = recording_writer();
+    client.respond_with(
+        ScriptedResponse::status(http::StatusCode::OK)
+            .with_body(r#"{"name":"influxdb","status":"pass","version":"v2.7.1"}"#),
+    );
+
+    let health = writer.health_blocking()?;
+    assert!(health.is_pass());
+    assert_eq!(Some("v2.7.1".to_owned()), health.version);
+    assert_eq!(
+        "http://localhost:8086/health",
+        client.requests()[0].uri.to_string()
+    );
+
+    #[cfg(all(feature = "test-server", feature = "ureq"))]
+    {
+        let server = influx_write::test_server::TestServer::start()?;
+        let mut writer =
+            influx_write::InfluxWriter::<influx_write::blocking::ureq::UreqClient>::new(
+                server.url(),
+                Authorization::token(MOCK_TOKEN)?,
+                MOCK_ORG,
+                MOCK_BUCKET,
+            )?;
+        assert_eq!(
+            Some("test-server".to_owned()),
+            writer.ping_blocking()?.version
+        );
+        assert!(writer.health_blocking()?.is_pass());
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_verify_creates_bucket() -> anyhow::Result<()> {
+    let mut server = mockito::Server::new_async().await;
+    let find_bucket = server
+        .mock("GET", influx_write::buckets::API_ENDPOINT_BUCKETS)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("org".into(), MOCK_ORG.into()),
+            Matcher::UrlEncoded("name".into(), MOCK_BUCKET.into()),
+        ]))
+        .with_body(r#"{"buckets":[]}"#)
+        .create();
+    let find_org = server
+        .mock("GET", influx_write::buckets::API_ENDPOINT_ORGS)
+        .match_query(Matcher::UrlEncoded("org".into(), MOCK_ORG.into()))
+        .with_body(format!(
+            r#"{{"orgs":[{{"id":"0123","name":"{MOCK_ORG}"}}]}}"#
+        ))
+        .create();
+    let create_bucket = server
+        .mock("POST", influx_write::buckets::API_ENDPOINT_BUCKETS)
+        .match_body(Matcher::Json(serde_json::json!({
+            "orgID": "0123",
+            "name": MOCK_BUCKET,
+            "retentionRules": [{"type": "expire", "everySeconds": 3600}]
+        })))
+        .with_status(201)
+        .with_body(format!(
+            r#"{{"id":"4567","orgID":"0123","name":"{MOCK_BUCKET}","retentionRules":[]}}"#
+        ))
+        .create();
+    let write = server
+        .mock("POST", influx_write::API_ENDPOINT_V2)
+        .match_query(Matcher::UrlEncoded("bucket".into(), MOCK_BUCKET.into()))
+        .with_status(400)
+        .with_body(r#"{"code":"invalid","message":"writing requires points"}"#)
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    client
+        .verify_or_create_bucket(Some(std::time::Duration::from_secs(3600)))
+        .await?;
+
+    find_bucket.assert();
+    find_org.assert();
+    create_bucket.assert();
+    write.assert();
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_blocking_errors() -> anyhow::Result<()> {
+    use influx_write::buckets::VerifyError;
+    use influx_write::recording::ScriptedResponse;
+
+    let (client, mut writer) = recording_writer();
+    client
+        .respond_with(ScriptedResponse::status(http::StatusCode::OK).with_body(r#"{"buckets":[]}"#))
+        .respond_with(
+            ScriptedResponse::status(http::StatusCode::OK).with_body(format!(
+                r#"{{"buckets":[{{"id":"4567","orgID":"0123","name":"{MOCK_BUCKET}"}}]}}"#
+            )),
+        )
+        .respond_with(ScriptedResponse::status(http::StatusCode::FORBIDDEN));
+
+    let error = writer.verify_blocking().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(VerifyError::BucketNotFound { .. })
+    ));
+
+    let error = writer.verify_blocking().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(VerifyError::WritePermissionDenied {
+            status: http::StatusCode::FORBIDDEN,
+            ..
+        })
+    ));
+
+    // an authorized write without points is rejected as invalid, which proves the permission
+    client.respond_with(
+        ScriptedResponse::status(http::StatusCode::OK).with_body(format!(
+            r#"{{"buckets":[{{"id":"4567","orgID":"0123","name":"{MOCK_BUCKET}"}}]}}"#
+        )),
+    );
+    writer.verify_blocking()?;
+    assert_eq!(5, client.request_count());
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_buckets_client() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use influx_write::buckets::{
+        BucketsClient, CreateBucketRequest, ListBucketsRequest, RetentionRule, UpdateBucketRequest,
+        API_ENDPOINT_BUCKETS,
+    };
+
+    let bucket = |retention: u64| {
+        format!(
+            r#"{{"id":"4567","orgID":"0123","name":"{MOCK_BUCKET}","retentionRules":[{{"type":"expire","everySeconds":{retention}}}]}}"#
+        )
+    };
+
+    let mut server = mockito::Server::new_async().await;
+    let create = server
+        .mock("POST", API_ENDPOINT_BUCKETS)
+        .match_header(
+            "authorization",
+            Matcher::Exact(format!("Token {MOCK_TOKEN}")),
+        )
+        .match_body(Matcher::Json(serde_json::json!({
+            "orgID": "0123",
+            "name": MOCK_BUCKET,
+            "description": "sensor data",
+            "retentionRules": [{"type": "expire", "everySeconds": 86400}]
+        })))
+        .with_status(201)
+        .with_body(bucket(86400))
+        .create();
+    let list = server
+        .mock("GET", API_ENDPOINT_BUCKETS)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("org".into(), MOCK_ORG.into()),
+            Matcher::UrlEncoded("limit".into(), "10".into()),
+        ]))
+        .with_body(format!(r#"{{"buckets":[{}]}}"#, bucket(86400)))
+        .create();
+    let update = server
+        .mock("PATCH", "/api/v2/buckets/4567")
+        .match_body(Matcher::Json(serde_json::json!({
+            "retentionRules": [{"type": "expire", "everySeconds": 3600}]
+        })))
+        .with_body(bucket(3600))
+        .create();
+    let get = server
+        .mock("GET", "/api/v2/buckets/4567")
+        .with_body(bucket(3600))
+        .create();
+    let delete = server
+        .mock("DELETE", "/api/v2/buckets/4567")
+        .with_status(204)
+        .create();
+
+    let mut client = BucketsClient::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+    )?;
+
+    let created = client
+        .create(
+            &CreateBucketRequest::new("0123", MOCK_BUCKET)
+                .with_description("sensor data")
+                .with_retention(Duration::from_secs(86400)),
+        )
+        .await?;
+    assert_eq!("4567", created.id);
+
+    let buckets = client
+        .list(
+            &ListBucketsRequest::default()
+                .with_org(MOCK_ORG)
+                .with_limit(10),
+        )
+        .await?;
+    assert_eq!(vec![created], buckets);
+
+    let updated = client
+        .update(
+            "4567",
+            &UpdateBucketRequest::default().with_retention(Duration::from_secs(3600)),
+        )
+        .await?;
+    assert_eq!(
+        vec![RetentionRule::expire(Duration::from_secs(3600))],
+        updated.retention_rules
+    );
+    assert_eq!(updated, client.get("4567").await?);
+
+    client.delete("4567").await?;
+
+    create.assert();
+    list.assert();
+    update.assert();
+    get.assert();
+    delete.assert();
+
+    Ok(())
+}
+
+#[test]
+fn test_buckets_client_blocking() -> anyhow::Result<()> {
+    use influx_write::buckets::BucketsClient;
+    use influx_write::recording::ScriptedResponse;
+
+    let client = RecordingClient::new();
+    client.respond_with(
+        ScriptedResponse::status(http::StatusCode::OK).with_body(format!(
+            r#"{{"orgs":[{{"id":"0123","name":"{MOCK_ORG}"}}]}}"#
+        )),
+    );
+    client.respond_with(ScriptedResponse::status(http::StatusCode::NOT_FOUND));
+
+    let mut buckets = BucketsClient::new_with_blocking_client(
+        client.clone(),
+        "http://localhost:8086".parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+    );
+
+    assert_eq!("0123", buckets.find_org_blocking(MOCK_ORG)?.id);
+    assert!(buckets.delete_blocking("4567").is_err());
+
+    let requests = client.requests();
+    assert_eq!(Some(MOCK_ORG.to_owned()), requests[0].query_param("org"));
+    assert_eq!(http::Method::DELETE, requests[1].method);
+    assert_eq!("/api/v2/buckets/4567", requests[1].uri.path());
+
+    // ids are a single path segment, they can not address another endpoint
+    assert!(buckets.get_blocking("../orgs?org=other").is_err());
+    let request = &client.requests()[2];
+    assert_eq!("/api/v2/buckets/..%2Forgs%3Forg=other", request.uri.path());
+    assert_eq!(None, request.uri.query());
+    assert!(buckets.delete_blocking("..").is_err());
+    assert_eq!(3, client.request_count());
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_delete() -> anyhow::Result<()> {
+    use chrono::{TimeZone, Utc};
+
+    use influx_write::delete::{DeletePredicate, API_ENDPOINT_DELETE};
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", API_ENDPOINT_DELETE)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("org".into(), MOCK_ORG.into()),
+            Matcher::UrlEncoded("bucket".into(), MOCK_BUCKET.into()),
+        ]))
+        .match_body(Matcher::Json(serde_json::json!({
+            "start": "2021-08-31T00:00:00Z",
+            "stop": "2021-08-31T12:00:00.500Z",
+            "predicate": "_measurement=\"airSensors\" AND sensor_id=\"TLM0201\""
+        })))
+        .with_status(204)
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    client
+        .delete(
+            Utc.with_ymd_and_hms(2021, 8, 31, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2021, 8, 31, 12, 0, 0).unwrap()
+                + chrono::Duration::milliseconds(500),
+            &DeletePredicate::new()
+                .measurement("airSensors")
+                .tag("sensor_id", "TLM0201"),
+        )
+        .await?;
+
+    mock.assert();
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_flux_query() -> anyhow::Result<()> {
+    use influx_write::query::API_ENDPOINT_QUERY;
+    use influx_write::Value;
+
+    let flux = r#"from(bucket: "MyBucket") |> range(start: -1h)"#;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", API_ENDPOINT_QUERY)
+        .match_query(Matcher::UrlEncoded("org".into(), MOCK_ORG.into()))
+        .match_header("accept", "application/csv")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "query": flux,
+            "type": "flux"
+        })))
+        .with_body(
+            "#datatype,string,long,dateTime:RFC3339,long,string,string\n\
+             #group,false,false,false,false,true,true\n\
+             #default,_result,,,,,\n\
+             ,result,table,_time,_value,_field,_measurement\n\
+             ,,0,2021-08-31T15:37:37Z,1,field,measurement\n\
+             ,,0,2021-08-31T15:37:47Z,2,field,measurement\n",
+        )
+        .expect(2)
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let tables = client.query(flux).await?;
+    assert_eq!(1, tables.len());
+    assert_eq!(Some("measurement"), tables[0].records[0].measurement());
+
+    let mut records = client.query_records(flux).await?;
+    let mut values = Vec::new();
+    while let Some(record) = records.next_record().await? {
+        values.push(record.value().cloned());
+    }
+    assert_eq!(
+        vec![Some(Value::Integer(1)), Some(Value::Integer(2))],
+        values
+    );
+
+    mock.assert();
+
+    Ok(())
+}
+
+#[cfg(feature = "ureq")]
+#[test]
+fn test_flux_query_blocking() -> anyhow::Result<()> {
+    use influx_write::query::API_ENDPOINT_QUERY;
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", API_ENDPOINT_QUERY)
+        .match_query(Matcher::UrlEncoded("org".into(), MOCK_ORG.into()))
+        .with_chunked_body(|w| {
+            w.write_all(b"#datatype,string,long,long\n,result,table,_value\n")?;
+            w.write_all(b",,0,1\n,,0,2\n")
+        })
+        .create();
+    let failing = server
+        .mock("POST", API_ENDPOINT_QUERY)
+        .match_query(Matcher::UrlEncoded("org".into(), "other".into()))
+        .with_status(404)
+        .with_body(r#"{"code":"not found","message":"organization not found"}"#)
+        .create();
+
+    let url = server.url().parse::<url::Url>()?;
+    let mut client = influx_write::InfluxWriter::<influx_write::blocking::ureq::UreqClient>::new(
+        url.clone(),
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let tables = client.query_blocking("from(bucket: \"b\")")?;
+    assert_eq!(1, tables.len());
+    assert_eq!(2, tables[0].records.len());
+
+    let mut client = influx_write::InfluxWriter::<influx_write::blocking::ureq::UreqClient>::new(
+        url,
+        Authorization::token(MOCK_TOKEN)?,
+        "other",
+        MOCK_BUCKET,
+    )?;
+    let error = client
+        .query_records_blocking("from(bucket: \"b\")")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("organization not found"));
+
+    mock.assert();
+    failing.assert();
+
+    Ok(())
+}
+
+#[cfg(feature = "ureq")]
+#[test]
+fn test_large_response_blocking() -> anyhow::Result<()> {
+    use influx_write::sql::{Row, API_ENDPOINT_QUERY_SQL};
+
+    // larger than the 10 MB ureq reads by default
+    let line = format!("{{\"host\":\"{}\"}}\n", "a".repeat(100));
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", API_ENDPOINT_QUERY_SQL)
+        .with_body(line.repeat(100_000))
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::blocking::ureq::UreqClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let rows = client.query_sql_blocking::<Row>("SELECT host FROM cpu")?;
+    assert_eq!(100_000, rows.len());
+
+    mock.assert();
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_sql_and_influxql_query() -> anyhow::Result<()> {
+    use influx_write::sql::{Row, API_ENDPOINT_QUERY_SQL, API_ENDPOINT_QUERY_V1};
+
+    #[derive(serde::Deserialize)]
+    struct Cpu {
+        host: String,
+        usage: f64,
+    }
+
+    let mut server = mockito::Server::new_async().await;
+    let sql_mock = server
+        .mock("POST", API_ENDPOINT_QUERY_SQL)
+        .match_header("authorization", format!("Token {MOCK_TOKEN}").as_str())
+        .match_body(Matcher::Json(serde_json::json!({
+            "db": MOCK_BUCKET,
+            "q": "SELECT host, usage FROM cpu",
+            "format": "jsonl"
+        })))
+        .with_body("{\"host\":\"a\",\"usage\":0.5}\n{\"host\":\"b\",\"usage\":1.5}\n")
+        .expect(2)
+        .create();
+    let influxql_mock = server
+        .mock("POST", API_ENDPOINT_QUERY_V1)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("db".into(), MOCK_BUCKET.into()),
+            Matcher::UrlEncoded("q".into(), "SELECT usage FROM cpu GROUP BY host".into()),
+        ]))
+        .with_body(
+            r#"{"results":[{"statement_id":0,"series":[{"name":"cpu","tags":{"host":"a"},"columns":["time","usage"],"values":[["2021-08-31T15:37:37Z",0.5]]}]}]}"#,
+        )
+        .create();
+
+    let mut client = influx_write::InfluxWriter::<influx_write::reqwest::ReqwestClient>::new(
+        server.url().parse()?,
+        Authorization::token(MOCK_TOKEN)?,
+        MOCK_ORG,
+        MOCK_BUCKET,
+    )?;
+
+    let rows = client
+        .query_sql::<Row>("SELECT host, usage FROM cpu")
+        .await?;
+    assert_eq!(Some("b"), rows[1]["host"].as_str());
+
+    let cpus = client
+        .query_sql::<Cpu>("SELECT host, usage FROM cpu")
+        .await?;
+    assert_eq!("a", cpus[0].host);
+    assert_eq!(1.5, cpus[1].usage);
+
+    let series = client
+        .query_influxql("SELECT usage FROM cpu GROUP BY host")
+        .await?;
+    let cpus: Vec<Cpu> = series[0].deserialize_rows()?;
+    assert_eq!("a", cpus[0].host);
+    assert_eq!(0.5, cpus[0].usage);
+
+    sql_mock.assert();
+    influxql_mock.assert();
+
+    Ok(())
+}
+
+#[test]
+fn test_default_tags() -> anyhow::Result<()> {
+    let (client, writer) = recording_writer();
+    let mut writer = writer
+        .with_default_tags([("host", "a"), ("region", "eu")])
+        .with_default_tag("service", "ingest");
+
+    writer.write_single_blocking(
+        DataPointBuilder::new("measurement")
+            .with_tag("host", "b")
+            .with_field("field", 0.)
+            .into(),
+    )?;
+
+    writer.set_default_tag("version", "1.2.3");
+    writer.remove_default_tag("region");
+    writer.write_single_blocking(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 1.)
+            .into(),
+    )?;
+
+    client.assert_point(
+        MOCK_BUCKET,
+        "measurement",
+        &[("host", "b"), ("region", "eu"), ("service", "ingest")],
+    );
+    client.assert_point(
+        MOCK_BUCKET,
+        "measurement",
+        &[("host", "a"), ("service", "ingest"), ("version", "1.2.3")],
+    );
+    assert!(!client.received_point(
+        MOCK_BUCKET,
+        "measurement",
+        &[("host", "a"), ("region", "eu")]
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn test_client_timestamps() -> anyhow::Result<()> {
+    use chrono::DateTime;
+    use influx_write::Timestamp;
+
+    let now = DateTime::from_timestamp(1630424257, 0).unwrap();
+    let earlier = DateTime::from_timestamp(1630424200, 0).unwrap();
+
+    let (client, writer) = recording_writer();
+    let mut writer = writer.with_client_timestamps(move || Timestamp::from(now));
+
+    writer.write_blocking(vec![
+        DataPointBuilder::new("untimed")
+            .with_field("field", 0.)
+            .into(),
+        DataPointBuilder::new("timed")
+            .with_field("field", 0.)
+            .with_time(earlier)
+            .into(),
+    ])?;
+
+    let mut lines: Vec<String> = client.requests()[0]
+        .body
+        .lines()
+        .map(str::to_owned)
+        .collect();
+    lines.sort();
+    assert_eq!(
+        vec![
+            "timed field=0 1630424200000000000",
+            "untimed field=0 1630424257000000000"
+        ],
+        lines
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_precision() -> anyhow::Result<()> {
+    use chrono::DateTime;
+    use influx_write::WritePrecision;
+
+    let (client, mut writer) = recording_writer();
+
+    writer.write_single_with_precision_blocking(
+        DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .with_time(DateTime::from_timestamp(1630424257, 250_000_000).unwrap())
+            .into(),
+        WritePrecision::Auto,
+    )?;
+
+    let request = &client.requests()[0];
+    assert_eq!(WritePrecision::MS, request.precision()?);
+    assert_eq!("measurement field=0 1630424257250", request.body);
+
+    Ok(())
+}
+
+#[test]
+fn test_rounding() -> anyhow::Result<()> {
+    use influx_write::{Rounding, Timestamp, WritePrecision};
+
+    let (client, writer) = recording_writer();
+    let mut writer = writer.with_rounding(Rounding::Strict);
+
+    let point = |nanos| {
+        DataPointBuilder::new("measurement")
+            .with_field("field", 0.)
+            .with_time(Timestamp::from_nanos(nanos))
+    };
+
+    assert!(writer
+        .write_single_with_precision_blocking(point(1_500_000_000).into(), WritePrecision::S)
+        .is_err());
+    writer.write_single_with_precision_blocking(
+        point(1_500_000_000)
+            .with_rounding(Rounding::HalfEven)
+            .into(),
+        WritePrecision::S,
+    )?;
+
+    assert_eq!(1, client.request_count());
+    assert_eq!("measurement field=0 2", client.requests()[0].body);
+
+    Ok(())
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_derive_into_data_point() {
+    use influx_write::{DataPoint, IntoDataPoint, Timestamp, Value};
+
+    #[derive(IntoDataPoint)]
+    #[influx(measurement = "sensor")]
+    struct Reading<'a> {
+        #[influx(tag, rename = "sensor_id")]
+        id: u32,
+        #[influx(tag)]
+        location: Option<&'a str>,
+        #[influx(field)]
+        temperature: f64,
+        #[influx(field)]
+        humidity: Option<f64>,
+        #[influx(field)]
+        status: &'a str,
+        #[influx(timestamp)]
+        time: Option<Timestamp>,
+        #[influx(skip)]
+        _raw: Vec<u8>,
+    }
+
+    let point: DataPoint = Reading {
+        id: 7,
+        location: None,
+        temperature: 21.5,
+        humidity: Some(40.),
+        status: "ok",
+        time: Some(Timestamp::from_nanos(1)),
+        _raw: vec![],
+    }
+    .into();
+
+    assert_eq!("sensor", point.measurement());
+    assert_eq!(vec![("sensor_id", "7")], point.tags().collect::<Vec<_>>());
+    assert_eq!(Some(&Value::Float(21.5)), point.field("temperature"));
+    assert_eq!(Some(&Value::Float(40.)), point.field("humidity"));
+    assert_eq!(Some(&Value::String("ok".to_owned())), point.field("status"));
+    assert_eq!(Some(Timestamp::from_nanos(1)), point.time());
+}
+
+#[test]
+fn test_schema_registry() -> anyhow::Result<()> {
+    use influx_write::schema::{FieldType, SchemaError, SchemaRegistry};
+    use influx_write::{ConversionError, Rounding, Timestamp, WritePrecision};
+
+    let schema = SchemaRegistry::new().with_learning();
+    let (client, writer) = recording_writer();
+    let mut writer = writer.with_schema(schema.clone());
+
+    writer.write_single_blocking(
+        DataPointBuilder::new("measurement")
+            .with_field("value", 1i64)
+            .into(),
+    )?;
+    let error = writer
+        .write_single_blocking(
+            DataPointBuilder::new("measurement")
+                .with_field("value", 1.)
+                .into(),
+        )
+        .unwrap_err();
+
+    assert!(matches!(
+        error.downcast_ref::<SchemaError>(),
+        Some(SchemaError::FieldTypeConflict { point: 0, field, .. }) if field == "value"
+    ));
+    assert_eq!(
+        Some(FieldType::Integer),
+        schema
+            .measurement("measurement")
+            .unwrap()
+            .field_type("value")
+    );
+    assert_eq!(1, client.request_count());
+
+    // a batch that fails to serialize is never sent, so it must not define the field type
+    let error = writer
+        .write_single_with_precision_blocking(
+            DataPointBuilder::new("measurement")
+                .with_field("other", 1.)
+                .with_time(Timestamp::from_nanos(1))
+                .with_rounding(Rounding::Strict)
+                .into(),
+            WritePrecision::S,
+        )
+        .unwrap_err();
+    assert!(error.downcast_ref::<ConversionError>().is_some());
+    assert_eq!(
+        None,
+        schema
+            .measurement("measurement")
+            .unwrap()
+            .field_type("other")
+    );
+    writer.write_single_blocking(
+        DataPointBuilder::new("measurement")
+            .with_field("other", true)
+            .into(),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_cardinality_guard() -> anyhow::Result<()> {
+    use influx_write::cardinality::{CardinalityGuard, OverflowAction};
+
+    let guard = CardinalityGuard::new(2).with_action(OverflowAction::Fallback("other".to_owned()));
+    let (client, writer) = recording_writer();
+    let mut writer = writer
+        .with_default_tag("host", "a")
+        .with_cardinality_guard(guard.clone());
+
+    writer.write_blocking((0..4).map(|i| {
+        DataPointBuilder::new("http")
+            .with_tag("request_id", i.to_string())
+            .with_field("duration", 0.5)
+            .into()
+    }))?;
+
+    client.assert_point(MOCK_BUCKET, "http", &[("host", "a"), ("request_id", "1")]);
+    client.assert_point(
+        MOCK_BUCKET,
+        "http",
+        &[("host", "a"), ("request_id", "other")],
+    );
+    assert!(!client.received_point(MOCK_BUCKET, "http", &[("request_id", "3")])?);
+    assert_eq!(2, guard.metrics().redirected);
+
+    Ok(())
+}
+
+#[test]
+fn test_cardinality_guard_unsent_batches() -> anyhow::Result<()> {
+    use influx_write::cardinality::{CardinalityGuard, OverflowAction};
+    use influx_write::schema::{SchemaError, SchemaRegistry};
+    use influx_write::{DataPoint, Value};
+
+    let guard = CardinalityGuard::new(2).with_action(OverflowAction::Drop);
+    let (client, writer) = recording_writer();
+    let mut writer = writer
+        .with_schema(SchemaRegistry::new().with_learning())
+        .with_cardinality_guard(guard.clone());
+    let point = |id: &str, status: Value| -> DataPoint {
+        DataPointBuilder::new("http")
+            .with_tag("request_id", id)
+            .with_field("status", status)
+            .into()
+    };
+
+    writer.write_single_blocking(point("a", Value::from(200)))?;
+    // the batch fails the schema, so its series must not take the last free one
+    assert!(writer
+        .write_single_blocking(point("b", Value::from("ok")))
+        .is_err());
+    assert_eq!(Some(&1), guard.metrics().series.get("http"));
+
+    writer.write_single_blocking(point("c", Value::from(200)))?;
+    // every point is dropped, an empty write would be rejected by the server
+    writer.write_single_blocking(point("d", Value::from(200)))?;
+
+    // the guard would drop "e", the error still refers to the position in the written batch
+    let error = writer
+        .write_blocking(vec![
+            point("e", Value::from(200)),
+            point("a", Value::from("ok")),
+        ])
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SchemaError>(),
+        Some(SchemaError::FieldTypeConflict { point: 1, .. })
+    ));
+
+    assert_eq!(2, client.requests().len());
+    assert_eq!(Some(&2), guard.metrics().series.get("http"));
+    assert_eq!(1, guard.metrics().dropped);
+
+    Ok(())
+}